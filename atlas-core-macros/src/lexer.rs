@@ -0,0 +1,282 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use crate::validate::{check_regex, Errors, Unique};
+use syn::{spanned::Spanned, Attribute, Data, DeriveInput, Fields, Ident, LitStr, Type};
+
+/// The attributes describing how a variant is matched, sorted by priority on ties.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum RuleKind {
+    Int,
+    Float,
+    Token,
+    Keyword,
+    Ident,
+    String,
+    Regex,
+}
+
+impl RuleKind {
+    fn from_attr(attr: &Attribute) -> Option<Self> {
+        let path = attr.path();
+        Some(if path.is_ident("int") {
+            RuleKind::Int
+        } else if path.is_ident("float") {
+            RuleKind::Float
+        } else if path.is_ident("token") {
+            RuleKind::Token
+        } else if path.is_ident("keyword") {
+            RuleKind::Keyword
+        } else if path.is_ident("ident") {
+            RuleKind::Ident
+        } else if path.is_ident("string") {
+            RuleKind::String
+        } else if path.is_ident("regex") {
+            RuleKind::Regex
+        } else {
+            return None;
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            RuleKind::Int => "int",
+            RuleKind::Float => "float",
+            RuleKind::Token => "token",
+            RuleKind::Keyword => "keyword",
+            RuleKind::Ident => "ident",
+            RuleKind::String => "string",
+            RuleKind::Regex => "regex",
+        }
+    }
+}
+
+/// A variant of the enum once its attributes are parsed.
+pub(crate) struct Variant {
+    pub(crate) ident: Ident,
+    pub(crate) kind: RuleKind,
+    /// The argument of `#[token]`, `#[keyword]` and `#[regex]`
+    pub(crate) text: Option<LitStr>,
+    pub(crate) field: Option<Type>,
+    pub(crate) skip: bool,
+}
+
+impl Variant {
+    fn parse(variant: &syn::Variant) -> syn::Result<Self> {
+        let mut kind: Option<(RuleKind, &Attribute)> = None;
+        let mut text = None;
+        let mut skip = false;
+        for attr in &variant.attrs {
+            if attr.path().is_ident("skip") {
+                attr.meta.require_path_only()?;
+                skip = true;
+                continue;
+            }
+            let Some(k) = RuleKind::from_attr(attr) else {
+                continue;
+            };
+            if let Some((previous, _)) = kind {
+                return Err(syn::Error::new(
+                    attr.span(),
+                    format!(
+                        "`{}` already has a `#[{}]` attribute, a variant can only be matched one way",
+                        variant.ident,
+                        previous.name()
+                    ),
+                ));
+            }
+            match k {
+                RuleKind::Token | RuleKind::Keyword | RuleKind::Regex => {
+                    let lit: LitStr = attr.parse_args()?;
+                    if lit.value().is_empty() {
+                        return Err(syn::Error::new(
+                            lit.span(),
+                            format!("`#[{}]` can't be empty", k.name()),
+                        ));
+                    }
+                    if k == RuleKind::Regex {
+                        let mut errors = Errors::default();
+                        check_regex(&mut errors, &lit);
+                        errors.finish()?;
+                    }
+                    text = Some(lit);
+                }
+                _ => {
+                    attr.meta.require_path_only()?;
+                }
+            }
+            kind = Some((k, attr));
+        }
+        let Some((kind, attr)) = kind else {
+            return Err(syn::Error::new(
+                variant.ident.span(),
+                format!(
+                    "`{}` needs one of `#[token(\"..\")]`, `#[keyword(\"..\")]`, `#[ident]`, `#[int]`, `#[float]`, `#[string]` or `#[regex(\"..\")]`",
+                    variant.ident
+                ),
+            ));
+        };
+        let field = match &variant.fields {
+            Fields::Unit => None,
+            Fields::Unnamed(f) if f.unnamed.len() == 1 => Some(f.unnamed[0].ty.clone()),
+            fields => {
+                return Err(syn::Error::new(
+                    fields.span(),
+                    "a token variant can only be a unit variant or have a single unnamed field",
+                ))
+            }
+        };
+        match (kind, &field) {
+            (RuleKind::Token | RuleKind::Keyword, Some(ty)) => {
+                return Err(syn::Error::new(
+                    ty.span(),
+                    format!(
+                        "`#[{}]` always matches the same text, `{}` can't hold a value",
+                        kind.name(),
+                        variant.ident
+                    ),
+                ))
+            }
+            (RuleKind::Ident | RuleKind::Int | RuleKind::Float | RuleKind::String, None) => {
+                return Err(syn::Error::new(
+                    attr.span(),
+                    format!(
+                        "`#[{}]` needs a variant with a single field to store the value, like `{}(..)`",
+                        kind.name(),
+                        variant.ident
+                    ),
+                ))
+            }
+            _ => {}
+        }
+        Ok(Self {
+            ident: variant.ident.clone(),
+            kind,
+            text,
+            field,
+            skip,
+        })
+    }
+}
+
+/// Parses the variants of the enum deriving `Lexer`.
+pub(crate) fn parse_variants(input: &DeriveInput) -> syn::Result<Vec<Variant>> {
+    let Data::Enum(data) = &input.data else {
+        return Err(syn::Error::new(
+            input.ident.span(),
+            "`Lexer` can only be derived for enums",
+        ));
+    };
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            input.generics.span(),
+            "`Lexer` can't be derived for generic enums",
+        ));
+    }
+    let mut errors: Option<syn::Error> = None;
+    let mut variants = vec![];
+    for v in &data.variants {
+        match Variant::parse(v) {
+            Ok(v) => variants.push(v),
+            Err(e) => match &mut errors {
+                Some(errors) => errors.combine(e),
+                None => errors = Some(e),
+            },
+        }
+    }
+    if let Some(e) = errors {
+        return Err(e);
+    }
+
+    let mut errors = Errors::default();
+    let mut texts = Unique::new("text");
+    for v in &variants {
+        if let (RuleKind::Token | RuleKind::Keyword, Some(text)) = (v.kind, &v.text) {
+            texts.insert(
+                &mut errors,
+                format!("{:?}", text.value()),
+                format!("`{}`", v.ident),
+                text.span(),
+            );
+        }
+    }
+    errors.finish()?;
+    Ok(variants)
+}
+
+pub(crate) fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+    let krate = crate::crate_path(&input.attrs)?;
+    let mut variants = parse_variants(&input)?;
+    // Stable sort, so declaration order is kept inside each kind of rule
+    variants.sort_by_key(|v| v.kind);
+
+    let entries = variants.iter().map(|v| {
+        let ident = &v.ident;
+        let pattern = match v.kind {
+            RuleKind::Int => quote!(#krate::lexer::dfa::INT),
+            RuleKind::Float => quote!(#krate::lexer::dfa::FLOAT),
+            RuleKind::Ident => quote!(#krate::lexer::dfa::IDENT),
+            RuleKind::String => quote!(#krate::lexer::dfa::STRING),
+            RuleKind::Token | RuleKind::Keyword | RuleKind::Regex => {
+                let text = v.text.as_ref().unwrap();
+                quote!(#text)
+            }
+        };
+        let method = match v.kind {
+            RuleKind::Token | RuleKind::Keyword => quote!(literal),
+            _ => quote!(regex),
+        };
+        let rule = match &v.field {
+            None => quote!(#krate::lexer::dfa::Rule::Token(#name::#ident)),
+            Some(ty) => {
+                let text = if v.kind == RuleKind::String {
+                    quote!(&s[1..s.len() - 1])
+                } else {
+                    quote!(s)
+                };
+                quote!(#krate::lexer::dfa::Rule::WithText(|s| {
+                    <#ty as #krate::lexer::lexeme::FromLexeme>::from_lexeme(#text).map(#name::#ident)
+                }))
+            }
+        };
+        quote!(v.#method(#pattern, #rule);)
+    });
+
+    let skipped: Vec<_> = variants
+        .iter()
+        .filter(|v| v.skip)
+        .map(|v| {
+            let ident = &v.ident;
+            match v.field {
+                None => quote!(#name::#ident),
+                Some(_) => quote!(#name::#ident(..)),
+            }
+        })
+        .collect();
+    let has_strings = variants.iter().any(|v| v.kind == RuleKind::String);
+    let is_skipped = if skipped.is_empty() {
+        quote!(false)
+    } else {
+        quote!(::core::matches!(self, #(#skipped)|*))
+    };
+
+    Ok(quote! {
+        impl #krate::lexer::lexeme::Lexer for #name {
+            const HAS_STRINGS: bool = #has_strings;
+
+            fn vocabulary() -> &'static #krate::lexer::dfa::Vocabulary<Self> {
+                static VOCABULARY: ::std::sync::OnceLock<#krate::lexer::dfa::Vocabulary<#name>> =
+                    ::std::sync::OnceLock::new();
+                VOCABULARY.get_or_init(|| {
+                    let mut v = #krate::lexer::dfa::Vocabulary::builder();
+                    #(#entries)*
+                    v.build()
+                })
+            }
+
+            fn is_skipped(&self) -> bool {
+                #is_skipped
+            }
+        }
+    })
+}
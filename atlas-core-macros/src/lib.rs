@@ -0,0 +1,86 @@
+/*
+ * atlas-core-macros by Gipson62
+ *
+ * Procedural macros of atlas-core, re-exported by the `atlas_core::prelude`
+*/
+
+#![warn(missing_docs, unused)]
+
+//! # atlas-core-macros
+//!
+//! Procedural macros used by `atlas-core`. You shouldn't depend on this crate directly,
+//! everything is re-exported by `atlas_core::prelude`.
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, Attribute, DeriveInput, LitStr, Path};
+
+mod lexer;
+mod spanned;
+mod validate;
+
+/// Derives `atlas_core::lexer::lexeme::Lexer` for an enum describing every token of a language.
+///
+/// Each variant needs exactly one of the following attributes:
+/// - `#[token("...")]`: A symbol matched literally, on a unit variant.
+/// - `#[keyword("...")]`: A keyword matched literally, on a unit variant. Keywords win over `#[ident]`.
+/// - `#[ident]`: An identifier (`[\p{Alphabetic}_][\p{Alphabetic}\p{N}_]*`), on a variant with one field.
+/// - `#[int]`: An integer literal (`[0-9]+`), on a variant with one field.
+/// - `#[float]`: A float literal (`[0-9]+\.[0-9]*`), on a variant with one field.
+/// - `#[string]`: A string literal (`"[^"]*"`), the field receives the text between the quotes.
+/// - `#[regex("...")]`: Any regular expression, on a unit variant or a variant with one field.
+///
+/// The field of a variant is built from the matched text with `FromLexeme`.
+/// Adding `#[skip]` to a variant drops its tokens from the output (e.g. whitespaces or comments).
+///
+/// The enum needs to be `Copy`, as the `Vocabulary` hands out copies of the unit variants.
+///
+/// The generated code refers to `::atlas_core`. If the crate is imported under another name,
+/// give its path with `#[atlas_core(crate = "path")]` on the enum.
+#[proc_macro_derive(Lexer, attributes(atlas_core, token, keyword, ident, int, float, string, regex, skip))]
+pub fn derive_lexer(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    lexer::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives `atlas_core::utils::span::Spanned` for a struct or an enum, typically a node of an AST.
+///
+/// - On a struct, the span is the one of the field marked `#[span]`, or else of the field named `span`.
+/// - On an enum, each variant is handled like a struct. A variant without such a field but with a
+///   single field delegates to it (e.g. `Expr::Binary(BinaryExpr)`).
+///
+/// The field can be a `Span` or anything implementing `Spanned`.
+/// Like `#[derive(Lexer)]`, the path of `atlas_core` can be given with `#[atlas_core(crate = "path")]`.
+#[proc_macro_derive(Spanned, attributes(atlas_core, span))]
+pub fn derive_spanned(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    spanned::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Validates the declarations of `lexer_builder!`, reporting duplicated or conflicting entries.
+///
+/// It's called by `lexer_builder!` itself, you shouldn't need to use it directly.
+#[doc(hidden)]
+#[proc_macro]
+pub fn validate_lexer(input: TokenStream) -> TokenStream {
+    let decl = parse_macro_input!(input as validate::LexerDecl);
+    validate::expand(decl).into()
+}
+
+/// Returns the path given by `#[atlas_core(crate = "path")]`, or `::atlas_core` without the attribute.
+fn crate_path(attrs: &[Attribute]) -> syn::Result<Path> {
+    let mut path = None;
+    for attr in attrs.iter().filter(|a| a.path().is_ident("atlas_core")) {
+        attr.parse_nested_meta(|meta| {
+            if !meta.path.is_ident("crate") {
+                return Err(meta.error("expected `crate = \"path\"`"));
+            }
+            path = Some(meta.value()?.parse::<LitStr>()?.parse::<Path>()?);
+            Ok(())
+        })?;
+    }
+    Ok(path.unwrap_or_else(|| syn::parse_quote!(::atlas_core)))
+}
@@ -0,0 +1,98 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{spanned::Spanned, Data, DeriveInput, Fields, Index, Member};
+
+/// Finds the field holding the span: the one marked `#[span]`, or else the one named `span`.
+///
+/// Returns `None` if there is no such field.
+fn span_field(fields: &Fields) -> syn::Result<Option<Member>> {
+    let mut marked = None;
+    for (i, field) in fields.iter().enumerate() {
+        let Some(attr) = field.attrs.iter().find(|a| a.path().is_ident("span")) else {
+            continue;
+        };
+        attr.meta.require_path_only()?;
+        if marked.is_some() {
+            return Err(syn::Error::new(
+                attr.span(),
+                "`#[span]` can only be used on one field",
+            ));
+        }
+        marked = Some(match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i)),
+        });
+    }
+    Ok(marked.or_else(|| {
+        fields
+            .iter()
+            .filter_map(|f| f.ident.as_ref())
+            .find(|ident| *ident == "span")
+            .map(|ident| Member::Named(ident.clone()))
+    }))
+}
+
+/// Generates `impl atlas_core::utils::span::Spanned` for the input.
+pub(crate) fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+    let krate = crate::crate_path(&input.attrs)?;
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let member = span_field(&data.fields)?.ok_or_else(|| {
+                syn::Error::new(
+                    name.span(),
+                    format!(
+                        "`{}` needs a field named `span` or marked with `#[span]`",
+                        name
+                    ),
+                )
+            })?;
+            quote!(#krate::utils::span::Spanned::span(&self.#member))
+        }
+        Data::Enum(data) => {
+            let mut arms = vec![];
+            for variant in &data.variants {
+                let ident = &variant.ident;
+                // A variant without a span field delegates to its only field
+                let member = match span_field(&variant.fields)? {
+                    Some(member) => member,
+                    None if variant.fields.len() == 1 => match &variant.fields.iter().next().unwrap().ident {
+                        Some(ident) => Member::Named(ident.clone()),
+                        None => Member::Unnamed(Index::from(0)),
+                    },
+                    None => {
+                        return Err(syn::Error::new(
+                            variant.span(),
+                            format!(
+                                "`{}` needs a field named `span` or marked with `#[span]`, or a single field to delegate to",
+                                ident
+                            ),
+                        ))
+                    }
+                };
+                arms.push(quote! {
+                    #name::#ident { #member: inner, .. } => #krate::utils::span::Spanned::span(inner),
+                });
+            }
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span,
+                "`Spanned` can't be derived for unions",
+            ))
+        }
+    };
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #krate::utils::span::Spanned for #name #ty_generics #where_clause {
+            fn span(&self) -> #krate::utils::span::Span {
+                #body
+            }
+        }
+    })
+}
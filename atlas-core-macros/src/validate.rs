@@ -0,0 +1,350 @@
+use proc_macro2::{Literal, Span, TokenStream};
+use quote::quote;
+use regex_automata::{
+    dfa::{dense, StartKind},
+    nfa::thompson,
+    MatchKind,
+};
+use std::collections::HashMap;
+use syn::{
+    braced, bracketed,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    Ident, LitBool, LitChar, LitStr, Path, Token,
+};
+
+/// Accumulates the errors found while validating a lexer declaration, so they're all reported at once.
+#[derive(Default)]
+pub(crate) struct Errors(Option<syn::Error>);
+
+impl Errors {
+    pub(crate) fn push(&mut self, span: Span, msg: impl std::fmt::Display) {
+        let e = syn::Error::new(span, msg);
+        match &mut self.0 {
+            Some(errors) => errors.combine(e),
+            None => self.0 = Some(e),
+        }
+    }
+
+    pub(crate) fn finish(self) -> syn::Result<()> {
+        match self.0 {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Remembers the first declaration of each entry to report the ones declared twice.
+pub(crate) struct Unique<'a> {
+    what: &'a str,
+    seen: HashMap<String, String>,
+}
+
+impl<'a> Unique<'a> {
+    pub(crate) fn new(what: &'a str) -> Self {
+        Self {
+            what,
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Registers `key`, declared by `owner`, reporting an error if it was already declared.
+    pub(crate) fn insert(&mut self, errors: &mut Errors, key: String, owner: String, span: Span) {
+        if let Some(first) = self.seen.get(&key) {
+            if *first == owner {
+                errors.push(span, format!("{} {} is declared twice", self.what, owner));
+            } else {
+                errors.push(
+                    span,
+                    format!(
+                        "{} {} is declared by both {} and {}",
+                        self.what, key, first, owner
+                    ),
+                );
+            }
+        } else {
+            self.seen.insert(key, owner);
+        }
+    }
+}
+
+/// Checks that `pattern` is a valid regular expression.
+pub(crate) fn check_regex(errors: &mut Errors, pattern: &LitStr) {
+    if let Err(e) = regex_syntax::parse(&pattern.value()) {
+        errors.push(
+            pattern.span(),
+            format!("invalid regular expression: {}", e),
+        );
+    }
+}
+
+/// Compiles `patterns` into a single DFA, configured like `atlas_core::lexer::pattern::PatternSet`.
+///
+/// Both have to stay in sync: the DFA is serialized here and loaded as a `PatternSet` at runtime.
+fn build_dfa(patterns: &[&str]) -> Result<dense::DFA<Vec<u32>>, String> {
+    dense::Builder::new()
+        .configure(
+            dense::Config::new()
+                .match_kind(MatchKind::All)
+                .start_kind(StartKind::Anchored),
+        )
+        .thompson(thompson::Config::new().shrink(true))
+        .build_many(patterns)
+        .map_err(|e| e.to_string())
+}
+
+/// Checks that `pattern` can be compiled to a DFA (e.g. `\b` can't, as a Unicode word boundary).
+fn check_dfa(errors: &mut Errors, pattern: &LitStr) {
+    if let Err(e) = build_dfa(&[&pattern.value()]) {
+        errors.push(
+            pattern.span(),
+            format!("the regular expression can't be compiled to a DFA: {}", e),
+        );
+    }
+}
+
+/// Returns the bytes of `dfa` serialized with `endianness`, in a `static` only compiled for it.
+fn serialized_dfa(krate: &Path, bytes: (Vec<u8>, usize), endianness: &str) -> TokenStream {
+    let (bytes, padding) = bytes;
+    let bytes = &bytes[padding..];
+    let len = bytes.len();
+    let literal = Literal::byte_string(bytes);
+    quote! {
+        /// The `Patterns` compiled to a DFA when the lexer is built, see `PatternSet::from_bytes`
+        #[cfg(target_endian = #endianness)]
+        pub static PATTERNS_DFA: #krate::lexer::pattern::AlignedBytes<[u8; #len]> =
+            #krate::lexer::pattern::AlignedBytes {
+                align: [],
+                bytes: *#literal,
+            };
+    }
+}
+
+/// Returns `true` if `c` can start an identifier of the default keyword system.
+pub(crate) fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+/// Returns `true` if `s` is an identifier of the default keyword system.
+fn is_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(is_ident_start) && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Parses `name:`, the key of an entry of the declaration.
+fn key(input: ParseStream, name: &str) -> syn::Result<()> {
+    let key: Ident = input.parse()?;
+    if key != name {
+        return Err(syn::Error::new(key.span(), format!("expected `{}`", name)));
+    }
+    input.parse::<Token![:]>()?;
+    Ok(())
+}
+
+fn list<T>(input: ParseStream, name: &str, f: fn(ParseStream) -> syn::Result<T>) -> syn::Result<Vec<T>> {
+    key(input, name)?;
+    let content;
+    bracketed!(content in input);
+    let items = Punctuated::<T, Token![;]>::parse_terminated_with(&content, f)?;
+    if !input.is_empty() {
+        input.parse::<Token![,]>()?;
+    }
+    Ok(items.into_iter().collect())
+}
+
+/// The declaration of a lexer built by `lexer_builder!`, as forwarded by the macro.
+pub(crate) struct LexerDecl {
+    /// The path of `atlas_core`, as given by `$crate`
+    krate: Path,
+    keyword_system: LitBool,
+    string_system: LitBool,
+    number_system: LitBool,
+    single: Vec<(LitChar, Ident)>,
+    either: Vec<(LitChar, LitChar, Ident, Ident)>,
+    patterns: Vec<(LitStr, Ident)>,
+    keywords: Vec<LitStr>,
+    trailing: Vec<(LitStr, Ident)>,
+    /// The rest of the lexer, only expanded if the declaration is valid.
+    expansion: TokenStream,
+}
+
+impl Parse for LexerDecl {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        key(input, "krate")?;
+        let krate = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let system = |name| -> syn::Result<LitBool> {
+            key(input, name)?;
+            let value = input.parse()?;
+            input.parse::<Token![,]>()?;
+            Ok(value)
+        };
+        let keyword_system = system("keyword_system")?;
+        let string_system = system("string_system")?;
+        let number_system = system("number_system")?;
+        Ok(Self {
+            krate,
+            keyword_system,
+            string_system,
+            number_system,
+            single: list(input, "single", |i| {
+                let c = i.parse()?;
+                i.parse::<Token![=>]>()?;
+                Ok((c, i.parse()?))
+            })?,
+            either: list(input, "either", |i| {
+                let c1 = i.parse()?;
+                i.parse::<Token![=>]>()?;
+                let c2 = i.parse()?;
+                i.parse::<Token![=>]>()?;
+                let v1 = i.parse()?;
+                i.parse::<Token![,]>()?;
+                Ok((c1, c2, v1, i.parse()?))
+            })?,
+            patterns: list(input, "patterns", |i| {
+                let p = i.parse()?;
+                i.parse::<Token![=>]>()?;
+                Ok((p, i.parse()?))
+            })?,
+            keywords: list(input, "keywords", |i| i.parse())?,
+            trailing: list(input, "trailing", |i| {
+                let s = i.parse()?;
+                i.parse::<Token![=>]>()?;
+                Ok((s, i.parse()?))
+            })?,
+            expansion: {
+                key(input, "expansion")?;
+                let content;
+                braced!(content in input);
+                content.parse()?
+            },
+        })
+    }
+}
+
+/// The variants `tokens!` always generates (in `TokenKind` or `TokenTag`), which can't be declared again.
+const BUILTIN_TOKEN_KINDS: &[&str] = &[
+    "Literal",
+    "Identifier",
+    "StringLiteral",
+    "Keyword",
+    "WhiteSpace",
+    "NewLine",
+    "Tabulation",
+    "CarriageReturn",
+    "EoI",
+    "SoI",
+];
+
+/// The variants of `Literal` generated by `tokens!`, which can't be used by the `trailing` suffixes.
+const BUILTIN_LITERALS: &[&str] = &["Int", "Float", "Bool", "Identifier", "StringLiteral"];
+
+impl LexerDecl {
+    pub(crate) fn validate(&self) -> syn::Result<()> {
+        let mut errors = Errors::default();
+
+        let mut variants = Unique::new("token kind");
+        let mut variant = |errors: &mut Errors, v: &Ident| {
+            if BUILTIN_TOKEN_KINDS.contains(&v.to_string().as_str()) {
+                errors.push(v.span(), format!("token kind `{}` is already generated by `tokens!`", v));
+            }
+            variants.insert(errors, v.to_string(), format!("`{}`", v), v.span());
+        };
+        let mut symbols = Unique::new("symbol");
+        // A symbol can't start like a token of another default system
+        let conflicts = |errors: &mut Errors, c: &LitChar, owner: &str| {
+            let conflict = match c.value() {
+                c if self.keyword_system.value && is_ident_start(c) => {
+                    "can also start an identifier, it conflicts with the keyword system"
+                }
+                '"' if self.string_system.value => "starts a string, it conflicts with the string system",
+                c if self.number_system.value && c.is_ascii_digit() => {
+                    "is a digit, it conflicts with the number system"
+                }
+                _ => return,
+            };
+            errors.push(c.span(), format!("symbol {:?} of {} {}", c.value(), owner, conflict));
+        };
+        for (c, v) in &self.single {
+            let owner = format!("`{}`", v);
+            variant(&mut errors, v);
+            conflicts(&mut errors, c, &owner);
+            symbols.insert(&mut errors, format!("{:?}", c.value()), owner, c.span());
+        }
+        for (c1, _, v1, v2) in &self.either {
+            let owner = format!("`{}, {}`", v1, v2);
+            variant(&mut errors, v1);
+            variant(&mut errors, v2);
+            conflicts(&mut errors, c1, &owner);
+            symbols.insert(&mut errors, format!("{:?}", c1.value()), owner, c1.span());
+        }
+        for (p, v) in &self.patterns {
+            variant(&mut errors, v);
+            if regex_syntax::parse(&p.value()).is_ok() {
+                check_dfa(&mut errors, p);
+            } else {
+                check_regex(&mut errors, p);
+            }
+        }
+
+        let mut keywords = Unique::new("keyword");
+        for k in &self.keywords {
+            let value = k.value();
+            if !is_ident(&value) {
+                errors.push(
+                    k.span(),
+                    format!("keyword {:?} isn't an identifier, the keyword system can never match it", value),
+                );
+            }
+            keywords.insert(&mut errors, format!("{:?}", value), format!("{:?}", value), k.span());
+        }
+
+        let mut suffixes = Unique::new("suffix");
+        let mut literals = Unique::new("literal kind");
+        for (s, v) in &self.trailing {
+            suffixes.insert(&mut errors, format!("{:?}", s.value()), format!("`{}`", v), s.span());
+            if BUILTIN_LITERALS.contains(&v.to_string().as_str()) {
+                errors.push(v.span(), format!("literal kind `{}` is already generated by `tokens!`", v));
+            }
+            literals.insert(&mut errors, v.to_string(), format!("`{}`", v), v.span());
+            if s.value().is_empty() {
+                errors.push(s.span(), format!("the suffix of `{}` can't be empty", v));
+            }
+        }
+
+        errors.finish()
+    }
+}
+
+impl LexerDecl {
+    /// Compiles the `Patterns` into a DFA, serialized for both endiannesses.
+    fn patterns_dfa(&self) -> syn::Result<TokenStream> {
+        let patterns: Vec<String> = self.patterns.iter().map(|(p, _)| p.value()).collect();
+        let patterns: Vec<&str> = patterns.iter().map(String::as_str).collect();
+        let dfa = build_dfa(&patterns).map_err(|e| {
+            let span = self.patterns.first().map_or_else(Span::call_site, |(p, _)| p.span());
+            syn::Error::new(span, format!("the patterns can't be compiled to a DFA: {}", e))
+        })?;
+        let little = serialized_dfa(&self.krate, dfa.to_bytes_little_endian(), "little");
+        let big = serialized_dfa(&self.krate, dfa.to_bytes_big_endian(), "big");
+        Ok(quote! {
+            #little
+            #big
+        })
+    }
+}
+
+/// Expands to the DFA of the `Patterns` and the rest of the lexer, or only to the errors so they
+/// aren't followed by the ones the invalid declaration would cause in the generated code.
+pub(crate) fn expand(decl: LexerDecl) -> TokenStream {
+    decl.validate()
+        .and_then(|()| decl.patterns_dfa())
+        .map(|dfa| {
+            let expansion = &decl.expansion;
+            quote! {
+                #dfa
+                #expansion
+            }
+        })
+        .unwrap_or_else(syn::Error::into_compile_error)
+}
@@ -0,0 +1,121 @@
+//! Compares the throughput of the system-based lexer (`AtlasLexer::tokenize`)
+//! with the table-driven DFA (`AtlasLexer::tokenize_dfa`), and its zero-copy
+//! variant (`AtlasLexer::tokenize_borrowed`). The DFA is compiled by the first call,
+//! which is measured on its own.
+//!
+//! Run it with `cargo bench --bench throughput`.
+
+use atlas_core::prelude::*;
+use std::time::{Duration, Instant};
+
+lexer_builder! {
+    DefaultSystem {
+        number: true,
+        symbol: true,
+        keyword: true,
+        string: true,
+        whitespace: {
+            allow_them: false,
+            use_system: true,
+        },
+    },
+    Symbols {
+        Single {
+            '(' => LParen,
+            ')' => RParen,
+            '{' => LBrace,
+            '}' => RBrace,
+            '[' => LBracket,
+            ']' => RBracket,
+            ',' => Comma,
+            '+' => OpAdd,
+            '/' => OpDiv,
+            '*' => OpMul,
+            '^' => OpPow,
+            '%' => OpMod,
+            ';' => Semicolon,
+        },
+        Either {
+            '=' => '=' => OpEq, OpAssign,
+            '!' => '=' => OpNEq, Bang,
+            '.' => '.' => DoubleDot, Dot,
+            ':' => ':' => DoubleColon, Colon,
+            '-' => '>' => RArrow, OpSub,
+            '<' => '=' => OpLessThanEq, OpLessThan,
+            '>' => '=' => OpGreaterThanEq, OpGreaterThan,
+        }
+    },
+    Keyword {
+        "then", "if", "else", "struct", "true", "false", "let", "import", "return", "enum", "end", "do",
+    },
+    Number {
+        trailing {
+            "_i64" => i64 => I64,
+        },
+        float: true,
+        u_int: true,
+        int: true
+    },
+}
+
+const SNIPPET: &str = r#"import "std/io"
+struct Point do
+    x: f64,
+    y: f64,
+end
+let distance = (a: Point, b: Point) -> f64 do
+    let dx = a.x - b.x;
+    let dy = a.y - b.y;
+    return (dx * dx + dy * dy) ^ 0.5;
+end
+let main = () -> int do
+    if distance(Point { x: 1.0, y: 2.0 }, Point { x: 4.5, y: 6.25 }) >= 5 then
+        print("far away");
+    else
+        print("close enough");
+    end
+    return 0;
+end
+"#;
+
+fn measure(name: &str, source: &str, f: fn(&mut AtlasLexer) -> usize) {
+    let mut runs = 0u32;
+    let mut total = Duration::ZERO;
+    let mut tokens = 0;
+    while total < Duration::from_secs(2) {
+        let mut lexer = AtlasLexer::default();
+        lexer.set_source(source.to_owned());
+        let start = Instant::now();
+        tokens = f(&mut lexer);
+        total += start.elapsed();
+        runs += 1;
+    }
+    let secs = total.as_secs_f64() / runs as f64;
+    println!(
+        "{:<8} {:>10.2} MB/s  ({} tokens, {:.2} ms per run, {} runs)",
+        name,
+        source.len() as f64 / secs / 1_000_000.0,
+        tokens,
+        secs * 1000.0,
+        runs
+    );
+}
+
+fn main() {
+    let source = SNIPPET.repeat(1_000_000 / SNIPPET.len());
+    println!("Tokenizing {} bytes", source.len());
+    // The first call also compiles the DFA (see `default_vocabulary`), the runs below reuse it
+    let mut lexer = AtlasLexer::default();
+    lexer.set_source(source.clone());
+    let start = Instant::now();
+    let tokens = lexer.tokenize_dfa().unwrap().len();
+    println!(
+        "{:<8} {:>10.2} ms    ({} tokens, first call, compiling the DFA)",
+        "dfa",
+        start.elapsed().as_secs_f64() * 1000.0,
+        tokens
+    );
+    measure("systems", &source, |l| l.tokenize().unwrap().len());
+    measure("dfa", &source, |l| l.tokenize_dfa().unwrap().len());
+    measure("borrowed", &source, |l| l.tokenize_borrowed().unwrap().len());
+}
@@ -0,0 +1,80 @@
+//! The lexer configuration of Atlas77, the language `atlas_core` was made for.
+
+use atlas_core::prelude::*;
+
+lexer_builder! {
+    DefaultSystem {
+        number: true,
+        symbol: true,
+        keyword: true,
+        string: true,
+        whitespace: {
+            allow_them: false,
+            use_system: true,
+        },
+    },
+    Symbols {
+        Single {
+            '(' => LParen,
+            ')' => RParen,
+            '{' => LBrace,
+            '}' => RBrace,
+            '[' => LBracket,
+            ']' => RBracket,
+            ',' => Comma,
+            '+' => OpAdd,
+            '/' => OpDiv,
+            '*' => OpMul,
+            '^' => OpPow,
+            '%' => OpMod,
+            '\\' => BackSlash,
+            ';' => Semicolon,
+            '\'' => Quote,
+            '?' => Interrogation,
+        },
+        Either {
+            '=' => '=' => OpEq, OpAssign,
+            '!' => '=' => OpNEq, Bang,
+            '.' => '.' => DoubleDot, Dot,
+            ':' => ':' => DoubleColon, Colon,
+            '-' => '>' => RArrow, OpSub,
+            '<' => '=' => OpLessThanEq, OpLessThan,
+            '>' => '=' => OpGreaterThanEq, OpGreaterThan,
+            '&' => '&' => OpAnd, Ampersand,
+            '|' => '|' => OpOr, Pipe,
+            '~' => '>' => FatArrow, Tilde,
+        }
+    },
+    Keyword {
+        "then", "if", "else", "struct", "true", "false", "let", "import", "return", "enum", "List", "end", "do",
+        "i8", "i16", "i32", "int", "i128", "u8", "u16", "u32", "u64", "u128", "f32", "f64", "string", "char",
+    },
+    Number {
+        trailing {
+            "_i8"   => i8   => I8,
+            "_i16"  => i16  => I16,
+            "_i32"  => i32  => I32,
+            "_i64"  => i64  => I64,
+            "_i128" => i128 => I128,
+            "_u8"   => u8   => U8,
+            "_u16"  => u16  => U16,
+            "_u32"  => u32  => U32,
+            "_u64"  => u64  => U64,
+            "_u128" => u128 => U128,
+            "_f32"  => f32  => F32,
+            "_f64"  => f64  => F64
+        },
+        float: true,
+        u_int: true,
+        int: true
+    },
+}
+
+/// Tokenizes the file `file` of `map`
+pub fn tokenize(map: &SourceMap, file: FileId) -> Result<Vec<Token>, Diagnostic> {
+    let mut lexer = AtlasLexer::default();
+    lexer
+        .set_file(file)
+        .set_source(String::from(map.source(file)));
+    lexer.tokenize()
+}
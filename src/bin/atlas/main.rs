@@ -0,0 +1,134 @@
+//! `atlas`, the command line interface of `atlas_core`.
+//!
+//! `atlas tokenize` lexes files (or the standard input) with the built-in Atlas77 lexer
+//! and prints their tokens, `atlas repl` starts a REPL doing the same for every input.
+//! Built with the `cli` feature.
+
+mod atlas77;
+mod output;
+mod repl;
+
+use atlas_core::prelude::*;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use output::{FileTokens, Format};
+use std::io::{self, IsTerminal, Read};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "atlas",
+    version,
+    about = "The command line interface of atlas_core"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Tokenizes files with the Atlas77 lexer and prints their tokens
+    #[command(visible_alias = "lex")]
+    Tokenize(TokenizeArgs),
+    /// Starts a REPL printing the tokens of every input
+    Repl {
+        /// How the tokens are printed
+        #[arg(short, long, value_enum, default_value_t = Format::Table)]
+        format: Format,
+    },
+}
+
+#[derive(Debug, Args)]
+struct TokenizeArgs {
+    /// The files to tokenize, the standard input if there are none or for `-` (at most once)
+    files: Vec<PathBuf>,
+    /// How the tokens are printed
+    #[arg(short, long, value_enum, default_value_t = Format::Table)]
+    format: Format,
+    /// When the diagnostics are colored
+    #[arg(long, value_enum, default_value_t = Color::Auto)]
+    color: Color,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Color {
+    /// When the standard error is a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+impl Color {
+    fn enabled(self) -> bool {
+        match self {
+            Color::Auto => io::stderr().is_terminal(),
+            Color::Always => true,
+            Color::Never => false,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match cli.command {
+        Command::Tokenize(args) => tokenize(args),
+        Command::Repl { format } => repl::run(format),
+    }
+}
+
+fn tokenize(args: TokenizeArgs) -> ExitCode {
+    let mut map = SourceMap::new();
+    let paths = if args.files.is_empty() {
+        vec![PathBuf::from("-")]
+    } else {
+        args.files
+    };
+    // The standard input can only be read once
+    if paths.iter().filter(|p| p.as_os_str() == "-").count() > 1 {
+        eprintln!("error: `-` (the standard input) can only be given once");
+        return ExitCode::from(2);
+    }
+    let mut files = vec![];
+    for path in &paths {
+        let loaded = if path.as_os_str() == "-" {
+            let mut source = String::new();
+            io::stdin()
+                .read_to_string(&mut source)
+                .map(|_| map.add("<stdin>", source))
+        } else {
+            map.load(path)
+        };
+        match loaded {
+            Ok(file) => files.push(file),
+            Err(err) => {
+                eprintln!("error: couldn't read `{}`: {}", path.display(), err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let mut tokens = vec![];
+    let mut failed = false;
+    for &file in &files {
+        match atlas77::tokenize(&map, file) {
+            Ok(t) => tokens.push((file, t)),
+            Err(diagnostic) => {
+                eprint!("{}", diagnostic.render(&map, args.color.enabled()));
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        return ExitCode::FAILURE;
+    }
+    let files: Vec<FileTokens<'_>> = tokens
+        .iter()
+        .map(|(file, tokens)| FileTokens {
+            file: *file,
+            tokens,
+        })
+        .collect();
+    print!("{}", output::format(&map, &files, args.format));
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,239 @@
+//! The formats the tokens can be printed in.
+
+use crate::atlas77::Token;
+use atlas_core::prelude::*;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fmt::Write;
+
+/// How the tokens are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// An aligned table with the kind, text, span and position of every token
+    Table,
+    /// A JSON array with an object per token
+    Json,
+    /// A `kind@start..end` line per token
+    Compact,
+}
+
+/// The tokens of a file, with what's needed to print them
+pub struct FileTokens<'a> {
+    pub file: FileId,
+    pub tokens: &'a [Token],
+}
+
+/// Formats the tokens of every file in `format`.
+///
+/// In the table and compact formats, the tokens of each file are preceded by its name
+/// when there are several files.
+pub fn format(map: &SourceMap, files: &[FileTokens<'_>], format: Format) -> String {
+    match format {
+        Format::Json => json(map, files),
+        Format::Table | Format::Compact => {
+            let mut out = String::new();
+            for (i, file) in files.iter().enumerate() {
+                if files.len() > 1 {
+                    if i > 0 {
+                        out.push('\n');
+                    }
+                    let _ = writeln!(out, "{}:", map.name(file.file));
+                }
+                if format == Format::Table {
+                    table(&mut out, map, file);
+                } else {
+                    compact(&mut out, file);
+                }
+            }
+            out
+        }
+    }
+}
+
+/// Returns the name of the kind of `token`, without its payload
+fn kind(token: &Token) -> String {
+    format!("{:?}", token.kind().tag())
+}
+
+fn text<'src>(map: &'src SourceMap, token: &Token) -> &'src str {
+    map.snippet(token.span()).unwrap_or_default()
+}
+
+/// Escapes the line endings and tabs of `text`, so a token stays on one line
+fn escape(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\n' | '\r' | '\t' => c.escape_default().to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+fn table(out: &mut String, map: &SourceMap, file: &FileTokens<'_>) {
+    let source = map.source(file.file);
+    let lines = map.get(file.file).map(|f| f.line_index());
+    let rows: Vec<[String; 4]> = file
+        .tokens
+        .iter()
+        .map(|token| {
+            let span = token.span();
+            let (line, col) = lines.map_or((0, 0), |l| l.line_col(source, span.start));
+            [
+                kind(token),
+                escape(text(map, token)),
+                format!("{}..{}", usize::from(span.start), usize::from(span.end)),
+                format!("{}:{}", line, col),
+            ]
+        })
+        .collect();
+    let header = ["KIND", "TEXT", "SPAN", "LINE:COL"].map(String::from);
+    let mut widths = [0; 4];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in std::iter::once(&header).chain(&rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+}
+
+fn compact(out: &mut String, file: &FileTokens<'_>) {
+    for token in file.tokens {
+        let span = token.span();
+        let _ = writeln!(
+            out,
+            "{}@{}..{}",
+            kind(token),
+            usize::from(span.start),
+            usize::from(span.end)
+        );
+    }
+}
+
+/// A token in the JSON format
+#[derive(Serialize)]
+struct JsonToken<'a> {
+    file: &'a str,
+    kind: String,
+    text: &'a str,
+    start: usize,
+    end: usize,
+    line: usize,
+    column: usize,
+}
+
+fn json(map: &SourceMap, files: &[FileTokens<'_>]) -> String {
+    let mut tokens = vec![];
+    for file in files {
+        let source = map.source(file.file);
+        let lines = map.get(file.file).map(|f| f.line_index());
+        for token in file.tokens {
+            let span = token.span();
+            let (line, column) = lines.map_or((0, 0), |l| l.line_col(source, span.start));
+            tokens.push(JsonToken {
+                file: map.name(file.file),
+                kind: kind(token),
+                text: text(map, token),
+                start: usize::from(span.start),
+                end: usize::from(span.end),
+                line,
+                column,
+            });
+        }
+    }
+    let mut out = serde_json::to_string_pretty(&tokens).unwrap_or_default();
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atlas77;
+
+    fn formatted(sources: &[(&str, &str)], format: Format) -> String {
+        let mut map = SourceMap::new();
+        let tokens: Vec<(FileId, Vec<Token>)> = sources
+            .iter()
+            .map(|&(name, source)| {
+                let file = map.add(name, source);
+                (file, atlas77::tokenize(&map, file).unwrap())
+            })
+            .collect();
+        let files: Vec<FileTokens<'_>> = tokens
+            .iter()
+            .map(|(file, tokens)| FileTokens { file: *file, tokens })
+            .collect();
+        super::format(&map, &files, format)
+    }
+
+    #[test]
+    fn test_table() {
+        // The type names are keywords, and the tab of the string is escaped to keep it on one line
+        assert_eq!(
+            formatted(&[("main.atlas", "let x: i8 =\n\"a\tb\";")], Format::Table),
+            "\
+KIND           TEXT    SPAN    LINE:COL
+SoI                    0..0    1:1
+Keyword        let     0..3    1:1
+Identifier     x       4..5    1:5
+Colon          :       5..6    1:6
+Keyword        i8      7..9    1:8
+OpAssign       =       10..11  1:11
+StringLiteral  \"a\\tb\"  12..17  2:1
+Semicolon      ;       17..18  2:6
+EoI                    18..18  2:7
+"
+        );
+    }
+
+    #[test]
+    fn test_compact() {
+        // Each file is preceded by its name when there are several of them
+        assert_eq!(
+            formatted(&[("a.atlas", "x"), ("b.atlas", "1.5")], Format::Compact),
+            "\
+a.atlas:
+SoI@0..0
+Identifier@0..1
+EoI@1..1
+
+b.atlas:
+SoI@0..0
+Literal@0..3
+EoI@3..3
+"
+        );
+        assert_eq!(formatted(&[("a.atlas", "x")], Format::Compact), "SoI@0..0\nIdentifier@0..1\nEoI@1..1\n");
+    }
+
+    #[test]
+    fn test_json() {
+        let out = formatted(&[("a.atlas", "x"), ("b.atlas", "\"é\"")], Format::Json);
+        let tokens: Vec<serde_json::Value> = serde_json::from_str(&out).unwrap();
+        assert_eq!(tokens.len(), 6);
+        // The keys are kept in the order of the fields, and the columns are counted in chars
+        assert!(out.contains("\"file\": \"a.atlas\",\n    \"kind\": \"SoI\",\n    \"text\": \"\""));
+        assert_eq!(
+            tokens[4],
+            serde_json::json!({
+                "file": "b.atlas",
+                "kind": "StringLiteral",
+                "text": "\"é\"",
+                "start": 0,
+                "end": 4,
+                "line": 1,
+                "column": 1,
+            })
+        );
+        assert_eq!((&tokens[5]["column"], &tokens[5]["start"]), (&serde_json::json!(4), &serde_json::json!(4)));
+    }
+}
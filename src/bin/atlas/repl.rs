@@ -0,0 +1,167 @@
+//! The REPL of `atlas`, reading Atlas77 with line editing and printing its tokens.
+//!
+//! There is no evaluator yet, so only the tokens of each input are shown.
+
+use crate::atlas77::{self, AtlasLexer, TokenKind};
+use crate::output::{self, FileTokens, Format};
+use atlas_core::prelude::*;
+use rustyline::{
+    completion::Completer,
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    history::DefaultHistory,
+    validate::{ValidationContext, ValidationResult, Validator},
+    Editor, Helper,
+};
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// The name of the history file, in the home directory
+const HISTORY_FILE: &str = ".atlas_history";
+
+/// Keeps reading the input while its brackets aren't balanced, so it can span several lines
+struct Brackets;
+
+impl Validator for Brackets {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        Ok(if depth(ctx.input()) > 0 {
+            ValidationResult::Incomplete
+        } else {
+            ValidationResult::Valid(None)
+        })
+    }
+}
+
+impl Completer for Brackets {
+    type Candidate = String;
+}
+
+impl Hinter for Brackets {
+    type Hint = String;
+}
+
+impl Highlighter for Brackets {}
+
+impl Helper for Brackets {}
+
+/// Returns the number of brackets opened and not closed in `input`.
+///
+/// It's negative as soon as a bracket is closed without being opened, since typing more can't
+/// balance the input anymore. An input that can't be tokenized counts as balanced, so its
+/// diagnostic is shown right away.
+fn depth(input: &str) -> isize {
+    let mut lexer = AtlasLexer::default();
+    lexer.set_source(String::from(input));
+    let Ok(tokens) = lexer.tokenize() else {
+        return 0;
+    };
+    let mut depth = 0;
+    for token in &tokens {
+        depth += match token.kind() {
+            TokenKind::LParen | TokenKind::LBrace | TokenKind::LBracket => 1,
+            TokenKind::RParen | TokenKind::RBrace | TokenKind::RBracket => -1,
+            _ => 0,
+        };
+        if depth < 0 {
+            break;
+        }
+    }
+    depth
+}
+
+/// Returns the path of the history file, `None` if the home directory is unknown
+fn history_path() -> Option<PathBuf> {
+    std::env::home_dir().map(|home| home.join(HISTORY_FILE))
+}
+
+/// Runs the REPL until `:quit` or the end of the input (Ctrl-D)
+pub fn run(format: Format) -> ExitCode {
+    let mut editor = match Editor::<Brackets, DefaultHistory>::new() {
+        Ok(editor) => editor,
+        Err(err) => {
+            eprintln!("error: couldn't start the REPL: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    editor.set_helper(Some(Brackets));
+    let history = history_path();
+    if let Some(path) = &history {
+        // The file doesn't exist on the first run
+        let _ = editor.load_history(path);
+    }
+
+    println!("Atlas77 REPL, `:quit` or Ctrl-D to exit");
+    let colored = io::stderr().is_terminal();
+    let mut map = SourceMap::new();
+    let code = loop {
+        let input = match editor.readline(">> ") {
+            Ok(input) => input,
+            // Ctrl-C only drops the current input
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                break ExitCode::FAILURE;
+            }
+        };
+        if input.trim().is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(input.as_str());
+        if matches!(input.trim(), ":q" | ":quit") {
+            break ExitCode::SUCCESS;
+        }
+
+        let file = map.add(format!("<repl:{}>", map.len() + 1), input);
+        match atlas77::tokenize(&map, file) {
+            Ok(tokens) => print!(
+                "{}",
+                output::format(
+                    &map,
+                    &[FileTokens {
+                        file,
+                        tokens: &tokens
+                    }],
+                    format
+                )
+            ),
+            Err(diagnostic) => eprint!("{}", diagnostic.render(&map, colored)),
+        }
+    };
+
+    if let Some(path) = &history {
+        if let Err(err) = editor.save_history(path) {
+            eprintln!(
+                "warning: couldn't save the history to `{}`: {}",
+                path.display(),
+                err
+            );
+        }
+    }
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::depth;
+
+    #[test]
+    fn test_depth() {
+        assert_eq!(depth(""), 0);
+        assert_eq!(depth("let x = (1 + 2);"), 0);
+        assert_eq!(depth("fn f() {"), 1);
+        assert_eq!(depth("fn f() {\n    let v = [(1"), 3);
+        assert_eq!(depth("fn f() {\n    let v = [(1)];\n}"), 0);
+        // The brackets of a string don't count
+        assert_eq!(depth("let s = \"(({[\";"), 0);
+        assert_eq!(depth("print(\")\""), 1);
+        // A bracket closed without being opened can't be balanced by what comes next
+        assert_eq!(depth(")"), -1);
+        assert_eq!(depth("x) {"), -1);
+        assert_eq!(depth("(x)) ("), -1);
+        // Nor can an input which doesn't tokenize, like an unterminated string
+        assert_eq!(depth("print(\"(("), 0);
+    }
+}
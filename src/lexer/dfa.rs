@@ -0,0 +1,142 @@
+use crate::lexer::pattern::PatternSet;
+
+/// The identifiers, shared by the keyword system of `lexer_builder!` and `#[derive(Lexer)]`
+pub const IDENT: &str = r"[\p{Alphabetic}_][\p{Alphabetic}\p{N}_]*";
+/// The integer literals, only made of ASCII digits
+pub const INT: &str = r"[0-9]+";
+/// The float literals, only made of ASCII digits
+pub const FLOAT: &str = r"[0-9]+\.[0-9]*";
+/// The string literals, quotes included
+pub const STRING: &str = r#""[^"]*""#;
+
+/// What a `Vocabulary` does with the text matched by one of its entries.
+#[derive(Debug, Clone, Copy)]
+pub enum Rule<K> {
+    /// The entry always produces the same token kind (symbols, keywords, whitespaces, ...)
+    Token(K),
+    /// The token kind is built from the matched text (identifiers, numbers, strings, ...).
+    ///
+    /// Returning `None` means the text can't be turned into a token (e.g. an integer overflowing).
+    WithText(fn(&str) -> Option<K>),
+}
+
+/// `Vocabulary` is the whole set of tokens of a lexer compiled into a single table-driven DFA.
+///
+/// Instead of asking every system in turn whether it recognises the next character,
+/// the vocabulary walks the input once and returns the longest entry matching it.
+/// On ties, the entry added first wins, which mirrors the order of the systems.
+#[derive(Debug, Clone)]
+pub struct Vocabulary<K> {
+    set: PatternSet,
+    rules: Vec<Rule<K>>,
+    /// The set added with [`VocabularyBuilder::compiled`], searched after `set`
+    compiled: Option<(&'static PatternSet, Vec<Rule<K>>)>,
+}
+
+impl<K: Copy> Vocabulary<K> {
+    /// Creates a new `VocabularyBuilder`
+    pub fn builder() -> VocabularyBuilder<K> {
+        VocabularyBuilder {
+            patterns: vec![],
+            rules: vec![],
+            compiled: None,
+        }
+    }
+
+    /// Returns the entry of the compiled set with the longest match at the start of `input`.
+    fn compiled_match(&self, input: &str) -> Option<(Rule<K>, usize)> {
+        let (set, rules) = self.compiled.as_ref()?;
+        set.longest_match(input).map(|(idx, len)| (rules[idx], len))
+    }
+
+    /// Returns the entry with the longest match at the start of `input`, and the length of the match.
+    fn longest_match(&self, input: &str) -> Option<(Rule<K>, usize)> {
+        let found = self.set.longest_match(input).map(|(idx, len)| (self.rules[idx], len));
+        match (found, self.compiled_match(input)) {
+            // The compiled set is added last, so it loses ties
+            (Some((rule, len)), Some((_, compiled))) if len >= compiled => Some((rule, len)),
+            (_, Some(compiled)) => Some(compiled),
+            (found, None) => found,
+        }
+    }
+
+    /// Matches the longest entry at the start of `input`.
+    ///
+    /// If the matched text is rejected by its rule, the longest match of the set added with
+    /// [`VocabularyBuilder::compiled`] is tried instead, even if it's shorter: like a system
+    /// returning `None`, the rule leaves the input to the entries added after it.
+    ///
+    /// # Returns
+    /// - `Some((kind, len))`: The token kind and the length in bytes of the match.
+    /// - `None`: If no entry matches the start of `input`, or if the matched text is rejected by its rule.
+    pub fn next_token(&self, input: &str) -> Option<(K, usize)> {
+        let apply = |(rule, len): (Rule<K>, usize)| {
+            let kind = match rule {
+                Rule::Token(k) => k,
+                Rule::WithText(f) => f(&input[..len])?,
+            };
+            Some((kind, len))
+        };
+        apply(self.longest_match(input)?).or_else(|| apply(self.compiled_match(input)?))
+    }
+
+    /// Returns the length in bytes of the longest entry matching the start of `input`,
+    /// even if the matched text is then rejected by its rule.
+    pub fn match_len(&self, input: &str) -> Option<usize> {
+        self.longest_match(input).map(|(_, len)| len)
+    }
+}
+
+/// Collects the entries of a `Vocabulary` before compiling them all at once.
+#[derive(Debug, Clone)]
+pub struct VocabularyBuilder<K> {
+    patterns: Vec<String>,
+    rules: Vec<Rule<K>>,
+    compiled: Option<(&'static PatternSet, Vec<Rule<K>>)>,
+}
+
+impl<K: Copy> VocabularyBuilder<K> {
+    /// Adds an entry matching exactly `text`
+    pub fn literal(&mut self, text: &str, rule: Rule<K>) -> &mut Self {
+        let pattern = text
+            .chars()
+            .map(|c| format!("\\x{{{:X}}}", c as u32))
+            .collect();
+        self.patterns.push(pattern);
+        self.rules.push(rule);
+        self
+    }
+
+    /// Adds an entry matching the regular expression `pattern`
+    pub fn regex(&mut self, pattern: &str, rule: Rule<K>) -> &mut Self {
+        self.patterns.push(pattern.to_owned());
+        self.rules.push(rule);
+        self
+    }
+
+    /// Adds a set of regular expressions compiled ahead of time, with the rule of each of its patterns.
+    ///
+    /// The set isn't compiled again, e.g. `lexer_builder!` shares the one of its `Patterns` system.
+    /// Its patterns come after every other entry: they lose the ties, whatever the order of the calls.
+    ///
+    /// # Panics
+    /// Panics if there isn't exactly one rule per pattern of `set`.
+    pub fn compiled(&mut self, set: &'static PatternSet, rules: Vec<Rule<K>>) -> &mut Self {
+        assert_eq!(set.len(), rules.len(), "every pattern of the set needs a rule");
+        self.compiled = Some((set, rules));
+        self
+    }
+
+    /// Compiles every entry into the `Vocabulary`.
+    ///
+    /// # Panics
+    /// Panics if one of the regular expressions is invalid.
+    pub fn build(&self) -> Vocabulary<K> {
+        let patterns: Vec<&str> = self.patterns.iter().map(String::as_str).collect();
+        Vocabulary {
+            set: PatternSet::new(&patterns),
+            rules: self.rules.clone(),
+            compiled: self.compiled.clone(),
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use crate::utils::span::BytePos;
+use std::ops::Range;
+
+/// `TextEdit` describes a change of a source: the bytes in `range` are replaced by `text`.
+///
+/// It's used by the lexers built with `lexer_builder!` to re-lex only the part of the source
+/// affected by the edit (see `AtlasLexer::relex`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    /// The range of bytes replaced in the old source
+    pub range: Range<usize>,
+    /// The text inserted in place of `range`
+    pub text: String,
+}
+
+impl TextEdit {
+    /// Creates a new `TextEdit` replacing `range` by `text`
+    pub fn new(range: Range<usize>, text: impl Into<String>) -> Self {
+        Self {
+            range,
+            text: text.into(),
+        }
+    }
+
+    /// Applies the edit to `source`.
+    ///
+    /// # Panics
+    /// Panics if the range is out of bounds or doesn't lie on char boundaries.
+    pub fn apply(&self, source: &mut String) {
+        source.replace_range(self.range.clone(), &self.text);
+    }
+
+    /// Returns the position in the new source where the inserted text ends
+    pub fn new_end(&self) -> BytePos {
+        BytePos::from(self.range.start + self.text.len())
+    }
+
+    /// Maps a position of the old source located after the edit to the new source.
+    ///
+    /// Positions before the edit are returned unchanged, positions inside it are moved to its end.
+    pub fn shift(&self, pos: BytePos) -> BytePos {
+        let pos = usize::from(pos);
+        if pos < self.range.start {
+            BytePos::from(pos)
+        } else if pos < self.range.end {
+            self.new_end()
+        } else {
+            BytePos::from(pos + self.text.len() - self.range.len())
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use crate::utils::{
+    diagnostic::{Diagnostic, Label},
+    error_codes::{A0001, A0002, A0003},
+    source_map::FileId,
+    span::{BytePos, ExpansionId, Span},
+};
+
+/// Builds the diagnostic reported when no token can be read at `pos` in `source`.
+///
+/// If the lexer reads strings (`strings`), a `"` that can't be read is the start of a string
+/// that is never closed, anything else is a character no token of the lexer starts with.
+pub fn unexpected_input(file: FileId, source: &str, pos: BytePos, strings: bool) -> Diagnostic {
+    let rest = &source[usize::from(pos)..];
+    let c = rest.chars().next().unwrap_or_default();
+    let span = |len: usize| Span {
+        start: pos,
+        end: pos.shift_by(len),
+        file,
+        expansion: ExpansionId::ROOT,
+    };
+    if strings && c == '"' {
+        let len = rest.find('\n').unwrap_or(rest.len());
+        Diagnostic::error("unterminated string")
+            .with_code(A0001)
+            .with_label(Label::primary(span(len), "this string is never closed"))
+            .with_help("add a `\"` at the end of the string")
+    } else {
+        Diagnostic::error(format!("unexpected character `{}`", c.escape_debug()))
+            .with_code(A0002)
+            .with_label(Label::primary(span(c.len_utf8()), "no token starts with this character"))
+    }
+}
+
+/// Builds the diagnostic reported when the text of a literal can't be turned into its value
+/// (e.g. an integer too large for its type).
+pub fn invalid_literal(span: Span, text: &str) -> Diagnostic {
+    Diagnostic::error(format!("invalid literal `{}`", text))
+        .with_code(A0003)
+        .with_label(Label::primary(span, "this literal can't be represented"))
+}
+
+/// Builds the diagnostic reported when the lexer panicked while tokenizing `file`, with the
+/// message given to `panic!`.
+pub fn lexer_panicked(file: FileId, message: &str) -> Diagnostic {
+    let start = Span {
+        start: BytePos::from(0),
+        end: BytePos::from(0),
+        file,
+        expansion: ExpansionId::ROOT,
+    };
+    Diagnostic::error("the lexer panicked")
+        .with_label(Label::primary(start, "while tokenizing this file"))
+        .with_note(message)
+}
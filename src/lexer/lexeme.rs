@@ -0,0 +1,158 @@
+use crate::lexer::{dfa::Vocabulary, error};
+use crate::utils::{
+    diagnostic::Diagnostic,
+    source_map::FileId,
+    span::{BytePos, ExpansionId, Span, SpanError, Spanned},
+};
+use internment::Intern;
+
+/// `Lexer` is implemented by `#[derive(Lexer)]` on an enum describing every token of a language.
+///
+/// The derive compiles the `#[token]`, `#[keyword]`, `#[ident]`, `#[int]`, `#[float]`, `#[string]`
+/// and `#[regex]` attributes of the variants into a single [`Vocabulary`], and `tokenize` walks
+/// the source with it.
+///
+/// # Example
+///
+/// ```
+/// use atlas_core::prelude::*;
+///
+/// #[derive(Lexer, Debug, Clone, Copy, PartialEq)]
+/// enum Tok {
+///     #[token("+")]
+///     Plus,
+///     #[keyword("let")]
+///     Let,
+///     #[ident]
+///     Ident(Intern<String>),
+///     #[int]
+///     Int(i64),
+///     #[regex(r"[ \t\r\n]+")]
+///     #[skip]
+///     Whitespace,
+/// }
+///
+/// let tokens = Tok::tokenize(FileId::default(), "let x + 1").unwrap();
+/// let kinds: Vec<Tok> = tokens.iter().map(|t| t.kind).collect();
+/// assert_eq!(
+///     kinds,
+///     vec![Tok::Let, Tok::Ident(Intern::new(String::from("x"))), Tok::Plus, Tok::Int(1)]
+/// );
+/// ```
+///
+/// Mistakes in the declarations are reported at compile time, on the faulty attribute:
+///
+/// ```compile_fail
+/// use atlas_core::prelude::*;
+///
+/// #[derive(Lexer, Debug, Clone, Copy, PartialEq)]
+/// enum Tok {
+///     // error: `#[token]` always matches the same text, `Plus` can't hold a value
+///     #[token("+")]
+///     Plus(i64),
+/// }
+/// ```
+pub trait Lexer: Copy + Sized + 'static {
+    /// `true` if one of the token kinds is a `#[string]`, so a `"` that can't be read is reported
+    /// as an unterminated string rather than an unexpected character.
+    const HAS_STRINGS: bool = false;
+
+    /// Returns the vocabulary compiled from the declarations of the token kinds.
+    fn vocabulary() -> &'static Vocabulary<Self>;
+
+    /// Returns `true` if tokens of this kind shouldn't appear in the output of `tokenize` (`#[skip]`).
+    fn is_skipped(&self) -> bool;
+
+    /// Tokenizes `source`, picking at each position the longest declared token.
+    ///
+    /// Stops at the first text that isn't a token (or a literal that can't be represented),
+    /// returning a `Diagnostic` pointing at it.
+    fn tokenize(file: FileId, source: &str) -> Result<Vec<Lexeme<Self>>, Diagnostic> {
+        let vocabulary = Self::vocabulary();
+        let mut tok = vec![];
+        let mut current_pos = BytePos::from(0);
+        while usize::from(current_pos) < source.len() {
+            let start = current_pos;
+            let rest = &source[usize::from(start)..];
+            let (kind, len) = vocabulary.next_token(rest).ok_or_else(|| match vocabulary.match_len(rest) {
+                Some(len) => {
+                    let span = Span {
+                        start,
+                        end: start.shift_by(len),
+                        file,
+                        expansion: ExpansionId::ROOT,
+                    };
+                    error::invalid_literal(span, &rest[..len])
+                }
+                None => error::unexpected_input(file, source, start, Self::HAS_STRINGS),
+            })?;
+            current_pos = start.shift_by(len);
+            if !kind.is_skipped() {
+                tok.push(Lexeme {
+                    kind,
+                    span: Span {
+                        start,
+                        end: current_pos,
+                        file,
+                        expansion: ExpansionId::ROOT,
+                    },
+                });
+            }
+        }
+        Ok(tok)
+    }
+}
+
+/// A token produced by a [`Lexer`], pairing its kind with its position in the source.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Lexeme<K> {
+    /// The kind of the token
+    pub kind: K,
+    /// The span of the token in the source
+    pub span: Span,
+}
+
+impl<K> Lexeme<K> {
+    /// Returns the text of the token in `source`, the source it was lexed from.
+    ///
+    /// Fails if the span of the token is out of the bounds of `source` or not on char boundaries.
+    pub fn text<'src>(&self, source: &'src str) -> Result<&'src str, SpanError> {
+        self.span.slice(source)
+    }
+}
+
+impl<K> Spanned for Lexeme<K> {
+    #[inline(always)]
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+
+/// Converts the text matched by a token into the payload of its variant.
+///
+/// Used by `#[derive(Lexer)]` for the variants holding a value (`#[ident]`, `#[int]`, ...).
+pub trait FromLexeme: Sized {
+    /// Builds the value from the matched text, `None` if it can't be represented.
+    fn from_lexeme(text: &str) -> Option<Self>;
+}
+
+impl FromLexeme for Intern<String> {
+    fn from_lexeme(text: &str) -> Option<Self> {
+        Some(Intern::new(text.to_owned()))
+    }
+}
+
+macro_rules! from_lexeme_parse {
+    ($($t:ty),*) => {
+        $(
+            impl FromLexeme for $t {
+                fn from_lexeme(text: &str) -> Option<Self> {
+                    text.parse().ok()
+                }
+            }
+        )*
+    };
+}
+
+from_lexeme_parse!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64, bool, char);
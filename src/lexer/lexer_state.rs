@@ -18,8 +18,12 @@ pub struct LexerState<'lex> {
     /// to allow lookahead operations. `Peekable` enables efficient peeking at the
     /// next character without advancing the iterator.
     txt: Peekable<Chars<'lex>>,
-    ///
-    pub path: &'static str,
+    /// The part of the source string that hasn't been consumed yet.
+    rest: &'lex str,
+    /// The file being tokenized, used to build the `Span` of each token.
+    pub file: FileId,
+    /// The position right after the furthest character looked at, see [`LexerState::furthest`].
+    furthest: BytePos,
 }
 
 impl<'lex> LexerState<'lex> {
@@ -28,28 +32,20 @@ impl<'lex> LexerState<'lex> {
     /// # Parameters
     /// - `current_pos`: The initial position in the source string, typically set to the start (0).
     /// - `txt`: A reference to the source string that the lexer will process.
+    /// - `file`: The file of the `SourceMap` the source string comes from.
     ///
     /// # Returns
     /// A new `LexerState` instance ready for use.
-    pub fn new(current_pos: BytePos, txt: &'lex str, path: &'static str) -> Self {
+    pub fn new(current_pos: BytePos, txt: &'lex str, file: FileId) -> Self {
         Self {
             current_pos,
             txt: txt.chars().peekable(),
-            path,
+            rest: txt,
+            file,
+            furthest: current_pos,
         }
     }
 
-    /// Advances the iterator to the next character in the source string, updating the
-    /// current position accordingly.
-    ///
-    /// # Returns
-    /// - `Some(char)`: The next character if one exists.
-    /// - `None`: If the iterator has reached the end of the source string.
-    pub fn next(&mut self) -> Option<char> {
-        self.current_pos = self.current_pos.shift_by(1);
-        self.txt.next()
-    }
-
     /// Peeks at the next character in the source string without advancing the iterator.
     ///
     /// # Returns
@@ -59,6 +55,65 @@ impl<'lex> LexerState<'lex> {
     /// Peeking allows lookahead operations, which are often necessary in lexical analysis
     /// to decide how to parse the next tokens without consuming them.
     pub fn peek(&mut self) -> Option<&char> {
+        let end = match self.txt.peek() {
+            Some(c) => self.current_pos.shift(*c),
+            None => self.current_pos.shift_by(1),
+        };
+        self.furthest = self.furthest.max(end);
         self.txt.peek()
     }
+
+    /// Returns the part of the source string that hasn't been consumed yet.
+    ///
+    /// Useful for systems that need to look further ahead than a single character,
+    /// like the regex based `Patterns`. Such a system must then tell how far it read with
+    /// [`LexerState::look_ahead`].
+    pub fn remaining(&self) -> &'lex str {
+        self.rest
+    }
+
+    /// Records that the system read the next `len` bytes of [`LexerState::remaining`].
+    ///
+    /// A `len` greater than the length of the remaining string means the system saw its end.
+    pub fn look_ahead(&mut self, len: usize) {
+        self.furthest = self.furthest.max(self.current_pos.shift_by(len));
+    }
+
+    /// Returns the position right after the furthest character the system looked at, or one
+    /// byte past the end of the source if it saw the end.
+    ///
+    /// A token only depends on the source up to there: `relex` uses it to know which tokens
+    /// an edit can change.
+    pub fn furthest(&self) -> BytePos {
+        self.furthest
+    }
+
+    /// Advances the state by `n` bytes, updating the current position accordingly.
+    ///
+    /// If `n` falls in the middle of a character, that character is consumed as well.
+    pub fn skip_bytes(&mut self, n: usize) {
+        let target = self.current_pos.shift_by(n);
+        while self.current_pos < target && self.next().is_some() {}
+    }
+}
+
+impl Iterator for LexerState<'_> {
+    type Item = char;
+
+    /// Advances the iterator to the next character in the source string, updating the
+    /// current position accordingly.
+    ///
+    /// # Returns
+    /// - `Some(char)`: The next character if one exists.
+    /// - `None`: If the iterator has reached the end of the source string.
+    fn next(&mut self) -> Option<char> {
+        let Some(ch) = self.txt.next() else {
+            self.furthest = self.furthest.max(self.current_pos.shift_by(1));
+            return None;
+        };
+        self.current_pos = self.current_pos.shift(ch);
+        self.furthest = self.furthest.max(self.current_pos);
+        self.rest = &self.rest[ch.len_utf8()..];
+        Some(ch)
+    }
 }
@@ -1,6 +1,61 @@
 /// TODO
 pub mod lexer_state;
+/// Contains the `Vocabulary`, a table-driven DFA built from the whole declaration of a lexer
+pub mod dfa;
+/// Contains the diagnostics reported by the lexers
+pub mod error;
+/// Contains `TextEdit`, used to re-lex only the part of a source affected by a change
+pub mod edit;
+/// Contains the `Lexer` trait implemented by `#[derive(Lexer)]`
+pub mod lexeme;
+/// Contains the `PatternSet` used by the regex based token rules
+pub mod pattern;
+/// Contains the `WorkerPool` used to tokenize a batch of files concurrently
+pub mod pool;
 /// To be done
+///
+/// The declarations are checked at compile time: duplicated keywords, keywords which aren't
+/// identifiers, symbols declared twice (or both in `Single` and `Either`), symbols that can also
+/// start an identifier, a string or a number, conflicting suffixes and invalid `Patterns` are
+/// rejected with an error naming the conflicting entries.
+///
+/// ```compile_fail
+/// use atlas_core::prelude::*;
+///
+/// lexer_builder! {
+///     DefaultSystem {
+///         number: true,
+///         symbol: true,
+///         keyword: true,
+///         string: true,
+///         whitespace: {
+///             allow_them: false,
+///             use_system: true,
+///         },
+///     },
+///     Symbols {
+///         Single {
+///             // error: symbol '=' is declared by both `Equal` and `OpEq, OpAssign`
+///             '=' => Equal,
+///         },
+///         Either {
+///             '=' => '=' => OpEq, OpAssign,
+///         }
+///     },
+///     Keyword {
+///         // error: keyword "List" is declared twice
+///         "List", "List",
+///     },
+///     Number {
+///         trailing {
+///             "_i8" => i8 => I8,
+///         },
+///         float: true,
+///         u_int: true,
+///         int: true
+///     },
+/// }
+/// ```
 #[macro_export]
 macro_rules! lexer_builder {
     (
@@ -21,6 +76,9 @@ macro_rules! lexer_builder {
                 $($sym2:literal => $sym3:literal => $variant1:ident, $variant2:ident ),* $(,)?
             }
         },
+        $(Patterns {
+            $($pattern:literal => $pattern_variant:ident),* $(,)?
+        },)?
         Keyword {
             $($x:literal),* $(,)?
         },
@@ -33,150 +91,440 @@ macro_rules! lexer_builder {
             int: $int:literal $(,)?
         }$(,)?
     ) => {
-        tokens!{
-            Symbols {
-                Single {
-                    $($sym => $variant),*
-                }, Either {
-                    $($sym2 => $sym3 => $variant1, $variant2),*
+        $crate::__validate_lexer! {
+            krate: $crate,
+            keyword_system: $keyword,
+            string_system: $string,
+            number_system: $number,
+            single: [$($sym => $variant);*],
+            either: [$($sym2 => $sym3 => $variant1, $variant2);*],
+            patterns: [$($($pattern => $pattern_variant);*)?],
+            keywords: [$($x);*],
+            trailing: [$($trail_name => $trail_enum);*],
+            expansion: {
+                tokens!{
+                    Symbols {
+                        Single {
+                            $($sym => $variant),*
+                        }, Either {
+                            $($sym2 => $sym3 => $variant1, $variant2),*
+                        }
+                    },
+                    Number {$($trail_enum($trail_type),)*},
+                    Patterns {$($($pattern_variant),*)?}
                 }
-            },
-            Number {$($trail_enum($trail_type),)*}
-        }
-        keywords!($($x,)*);
-        pub type System = fn(char, &mut LexerState) -> Option<Token>;
-        #[derive(Debug, Default)]
-        pub struct AtlasLexer {
-            sys: Vec<System>,
-            path: &'static str,
-            pub current_pos: BytePos,
-            pub source: String,
-        }
-        impl AtlasLexer {
-            pub fn default() -> Self {
-                let mut lexer = AtlasLexer::new("<stdin>", String::new());
-                if $number {lexer.add_system(default_number);}
-                if $symbol {lexer.add_system(default_symbol);}
-                if $keyword {lexer.add_system(default_keyword);}
-                if $whitespace {lexer.add_system(default_whitespace);}
-                if $string {lexer.add_system(default_string);}
-                lexer
-            }
-            pub fn new(path: &'static str, source: String) -> Self {
-                Self {
-                    sys: vec![],
-                    path,
-                    current_pos: BytePos::from(0),
-                    source,
+                keywords!($($x,)*);
+                pub type System = fn(char, &mut LexerState) -> Option<Token>;
+                #[derive(Debug, Default, Clone)]
+                pub struct AtlasLexer {
+                    sys: Vec<System>,
+                    file: FileId,
+                    pub current_pos: BytePos,
+                    pub source: String,
+                    /// For each token returned by the last `tokenize` or `relex`, the end of its span and the
+                    /// position right after the furthest character read to produce it and the whitespaces
+                    /// dropped before it.
+                    lookahead: Vec<(BytePos, BytePos)>,
                 }
-            }
+                impl AtlasLexer {
+                    pub fn default() -> Self {
+                        let mut lexer = AtlasLexer::new(FileId::default(), String::new());
+                        if $number {lexer.add_system(default_number);}
+                        if $symbol {lexer.add_system(default_symbol);}
+                        if $keyword {lexer.add_system(default_keyword);}
+                        if $whitespace {lexer.add_system(default_whitespace);}
+                        if $string {lexer.add_system(default_string);}
+                        if !PATTERNS.is_empty() {lexer.add_system(default_pattern);}
+                        lexer
+                    }
+                    pub fn new(file: FileId, source: String) -> Self {
+                        Self {
+                            sys: vec![],
+                            file,
+                            current_pos: BytePos::from(0),
+                            source,
+                            lookahead: vec![],
+                        }
+                    }
 
-            pub fn set_source(&mut self, source: String) -> &mut Self {
-                self.source = source;
-                self
-            }
+                    pub fn set_source(&mut self, source: String) -> &mut Self {
+                        self.source = source;
+                        self
+                    }
 
-            pub fn set_path(&mut self, new_path: &'static str) -> &mut Self {
-                self.path = new_path;
-                self
-            }
+                    /// Sets the file of the `SourceMap` the source comes from, used in the `Span` of the tokens
+                    pub fn set_file(&mut self, new_file: FileId) -> &mut Self {
+                        self.file = new_file;
+                        self
+                    }
 
-            pub fn add_system(&mut self, s: fn(char, &mut LexerState) -> Option<Token>) -> &mut Self {
-                self.sys.push(s);
-                self
-            }
+                    pub fn add_system(&mut self, s: fn(char, &mut LexerState) -> Option<Token>) -> &mut Self {
+                        self.sys.push(s);
+                        self
+                    }
 
-            //A way of handling errors will come later
-            pub fn tokenize(&mut self) -> Result<Vec<Token>, ()> {
-                let mut tok: Vec<Token> = vec![];
-                tok.push(Token::new(
-                    Span {
-                        start: self.current_pos,
-                        end: self.current_pos,
-                        path: self.path,
-                    },
-                    TokenKind::SoI,
-                ));
-                loop {
-                    let ch = self.source.chars().nth(usize::from(self.current_pos));
-                    match ch {
-                        Some(c) => {
-                            let state = LexerState::new(
-                                self.current_pos,
-                                self.source
-                                    .get(usize::from(self.current_pos)..self.source.len())
-                                    .unwrap(),
-                                self.path,
-                            );
-                            let mut counter = 0;
-                            for f in &self.sys {
-                                let mut current_state = state.clone();
-                                match f(c, &mut current_state) {
-                                    Some(f) => {
-                                        if !$allow_whitespace {
-                                            match f.kind() {
-                                                TokenKind::WhiteSpace => {}
-                                                TokenKind::CarriageReturn => {}
-                                                TokenKind::NewLine => {}
-                                                TokenKind::Tabulation => {}
-                                                _ => tok.push(f),
-                                            }
-                                        } else {
-                                            tok.push(f);
-                                        }
-                                        self.current_pos = current_state.current_pos;
-                                        break;
-                                    }
-                                    None => {
-                                        counter += 1;
-                                        continue;
+                    /// Tokenizes the source, picking at each position the longest token produced by the systems.
+                    ///
+                    /// If several systems produce a token of the same length, the one added first wins.
+                    pub fn tokenize(&mut self) -> Result<Vec<Token>, Diagnostic> {
+                        let mut tok: Vec<Token> = vec![];
+                        tok.push(Token::new(
+                            Span {
+                                start: self.current_pos,
+                                end: self.current_pos,
+                                file: self.file,
+                                expansion: ExpansionId::ROOT,
+                            },
+                            TokenKind::SoI,
+                        ));
+                        self.lookahead.clear();
+                        let mut furthest = self.current_pos;
+                        while usize::from(self.current_pos) < self.source.len() {
+                            let (f, end, read) = self.longest_token(self.current_pos).ok_or_else(|| self.no_token(self.current_pos))?;
+                            furthest = furthest.max(read);
+                            if Self::is_kept(&f) {
+                                tok.push(f);
+                                self.lookahead.push((end, furthest));
+                                furthest = end;
+                            }
+                            self.current_pos = end;
+                        }
+                        tok.push(Token::new(
+                            Span {
+                                start: self.current_pos,
+                                end: self.current_pos,
+                                file: self.file,
+                                expansion: ExpansionId::ROOT,
+                            },
+                            TokenKind::EoI,
+                        ));
+                        Ok(tok)
+                    }
+
+                    /// Returns the longest token produced by the systems at `pos`, the position right after it,
+                    /// and the position right after the furthest character any of the systems read.
+                    fn longest_token(&self, pos: BytePos) -> Option<(Token, BytePos, BytePos)> {
+                        let rest = &self.source[usize::from(pos)..];
+                        let c = rest.chars().next()?;
+                        let state = LexerState::new(pos, rest, self.file);
+                        let mut longest: Option<(Token, BytePos)> = None;
+                        let mut furthest = pos.shift(c);
+                        for f in &self.sys {
+                            let mut current_state = state.clone();
+                            let token = f(c, &mut current_state);
+                            furthest = furthest.max(current_state.furthest());
+                            if let Some(f) = token {
+                                match longest {
+                                    Some((_, end)) if end >= current_state.current_pos => {}
+                                    _ => longest = Some((f, current_state.current_pos)),
+                                }
+                            }
+                        }
+                        longest.map(|(token, end)| (token, end, furthest))
+                    }
+
+                    /// Builds the diagnostic reported when no token can be produced at `pos`.
+                    ///
+                    /// The text is first matched against the declarations, like [`AtlasLexer::tokenize_dfa`]
+                    /// does, to tell a literal that can't be represented (e.g. an integer too large) from
+                    /// a character no token starts with.
+                    fn no_token(&self, pos: BytePos) -> Diagnostic {
+                        let rest = &self.source[usize::from(pos)..];
+                        let vocabulary = default_vocabulary();
+                        match vocabulary.match_len(rest) {
+                            Some(len) if vocabulary.next_token(rest).is_none() => {
+                                let span = Span {
+                                    start: pos,
+                                    end: pos.shift_by(len),
+                                    file: self.file,
+                                    expansion: ExpansionId::ROOT,
+                                };
+                                $crate::lexer::error::invalid_literal(span, &rest[..len])
+                            }
+                            _ => $crate::lexer::error::unexpected_input(self.file, &self.source, pos, $string),
+                        }
+                    }
+
+                    /// Returns `false` for the whitespaces when they aren't allowed in the output
+                    fn is_kept(token: &Token) -> bool {
+                        $allow_whitespace || !matches!(
+                            token.kind(),
+                            TokenKind::WhiteSpace
+                                | TokenKind::CarriageReturn
+                                | TokenKind::NewLine
+                                | TokenKind::Tabulation
+                        )
+                    }
+
+                    /// Applies `edit` to the source and re-lexes only the region it affects.
+                    ///
+                    /// `tokens` must be the result of the last `tokenize` or `relex` of this lexer, right before
+                    /// the edit: the lexer remembers how far it read the source to produce each of them. The tokens
+                    /// which were produced without reading the edited range are reused as they are, then the lexer
+                    /// restarts after them and runs until it reaches a position where an old token started after
+                    /// the edit: from there, the old tokens are reused with their `Span` shifted. The result is the
+                    /// same as a full `tokenize`.
+                    ///
+                    /// The lexer also remembers the file and the end of each token, and falls back to a full
+                    /// `tokenize` if `tokens` don't have the same ones. Only the spans are compared though: the
+                    /// tokens of another run ending at the same positions aren't detected, and give a wrong result.
+                    ///
+                    /// # Panics
+                    /// Panics if the range of the edit is out of bounds or doesn't lie on char boundaries.
+                    pub fn relex(&mut self, tokens: &[Token], edit: &TextEdit) -> Result<Vec<Token>, Diagnostic> {
+                        edit.apply(&mut self.source);
+                        // The tokens between `SoI` and `EoI`
+                        let real = tokens.get(1..tokens.len().saturating_sub(1)).unwrap_or(&[]);
+                        let lookahead = std::mem::take(&mut self.lookahead);
+                        let last_run = lookahead.len() == real.len()
+                            && real.iter().zip(&lookahead).all(|(t, &(end, _))| t.span.end == end && t.span.file == self.file);
+                        if !last_run {
+                            self.current_pos = BytePos::from(0);
+                            return self.tokenize();
+                        }
+                        // A token can be reused if neither it nor any token before it read the edited range
+                        let mut furthest = BytePos::from(0);
+                        let restart = lookahead
+                            .iter()
+                            .take_while(|&&(_, read)| {
+                                furthest = furthest.max(read);
+                                usize::from(furthest) <= edit.range.start
+                            })
+                            .count();
+                        let mut tok: Vec<Token> = vec![tokens.first().copied().unwrap_or(Token::new(
+                            Span {
+                                start: BytePos::from(0),
+                                end: BytePos::from(0),
+                                file: self.file,
+                                expansion: ExpansionId::ROOT,
+                            },
+                            TokenKind::SoI,
+                        ))];
+                        tok.extend_from_slice(&real[..restart]);
+                        self.lookahead.extend_from_slice(&lookahead[..restart]);
+                        // The end of a token is always a position the lexer went through
+                        self.current_pos = tok[tok.len() - 1].span.end;
+
+                        let mut furthest = self.current_pos;
+                        let mut old = real[restart..].iter().zip(&lookahead[restart..]).peekable();
+                        while usize::from(self.current_pos) < self.source.len() {
+                            if self.current_pos >= edit.new_end() {
+                                // Both sources are the same from here, so if an old token started at this
+                                // position, the rest of the old tokens can be reused
+                                while old
+                                    .next_if(|(t, _)| {
+                                        usize::from(t.span.start) < edit.range.end
+                                            || edit.shift(t.span.start) < self.current_pos
+                                    })
+                                    .is_some()
+                                {}
+                                if old.peek().is_some_and(|(t, _)| edit.shift(t.span.start) == self.current_pos) {
+                                    for (i, (t, &(_, read))) in old.enumerate() {
+                                        tok.push(Token::new(
+                                            Span {
+                                                start: edit.shift(t.span.start),
+                                                end: edit.shift(t.span.end),
+                                                file: t.span.file,
+                                                expansion: ExpansionId::ROOT,
+                                            },
+                                            t.kind,
+                                        ));
+                                        // The whitespaces before the first one were lexed again
+                                        let read = edit.shift(read);
+                                        self.lookahead.push((edit.shift(t.span.end), if i == 0 { furthest.max(read) } else { read }));
                                     }
+                                    self.current_pos = BytePos::from(self.source.len());
+                                    break;
                                 }
                             }
-                            if counter >= self.sys.len() {
-                                return Err(());
+                            let (f, end, read) = self.longest_token(self.current_pos).ok_or_else(|| self.no_token(self.current_pos))?;
+                            furthest = furthest.max(read);
+                            if Self::is_kept(&f) {
+                                tok.push(f);
+                                self.lookahead.push((end, furthest));
+                                furthest = end;
                             }
+                            self.current_pos = end;
                         }
-                        None => break,
+                        tok.push(Token::new(
+                            Span {
+                                start: self.current_pos,
+                                end: self.current_pos,
+                                file: self.file,
+                                expansion: ExpansionId::ROOT,
+                            },
+                            TokenKind::EoI,
+                        ));
+                        Ok(tok)
                     }
-                }
-                tok.push(Token::new(
-                    Span {
-                        start: self.current_pos,
-                        end: self.current_pos,
-                        path: self.path,
-                    },
-                    TokenKind::EoI,
-                ));
-                return Ok(tok);
-            }
-        }
-        pub fn default_number(c: char, state: &mut LexerState) -> Option<Token> {
-            if c.is_numeric() {
-                let start = state.current_pos;
-                let mut is_float = false;
-                let mut n = String::new();
-                n.push(c);
-                state.next();
-                loop {
-                    if let Some(c) = state.peek() {
-                        if c.is_numeric() {
-                            n.push(*c);
-                            state.next();
-                        } else {
-                            break;
+
+                    /// Tokenizes a batch of files concurrently, each one with the systems of this lexer.
+                    ///
+                    /// The files are handed out to the worker threads of `WorkerPool::global`, which are
+                    /// started once and reused by every batch, and the results are returned in the same order
+                    /// as the input. The `Intern` pool used for identifiers and strings is global and
+                    /// thread-safe, so it's shared by every worker. If the lexer panics on a file, only that
+                    /// file fails, with the diagnostic built by `error::lexer_panicked`.
+                    /// Called from one of the worker threads, the files are tokenized on that thread instead.
+                    pub fn tokenize_batch(&self, files: Vec<(FileId, String)>) -> Vec<Result<Vec<Token>, Diagnostic>> {
+                        let ids: Vec<FileId> = files.iter().map(|(file, _)| *file).collect();
+                        let sys = self.sys.clone();
+                        $crate::lexer::pool::WorkerPool::global()
+                            .map(files, move |(file, source)| {
+                                let mut lexer = AtlasLexer {
+                                    sys: sys.clone(),
+                                    file,
+                                    current_pos: BytePos::from(0),
+                                    source,
+                                    lookahead: vec![],
+                                };
+                                lexer.tokenize()
+                            })
+                            .into_iter()
+                            .zip(ids)
+                            .map(|(result, file)| {
+                                result.unwrap_or_else(|message| {
+                                    Err($crate::lexer::error::lexer_panicked(file, &message))
+                                })
+                            })
+                            .collect()
+                    }
+
+                    /// Tokenizes the source with the table-driven DFA compiled from the declarations
+                    /// (see [`default_vocabulary`]) instead of going through the systems.
+                    ///
+                    /// It produces the same tokens and diagnostics as [`AtlasLexer::tokenize`] with the
+                    /// default systems, but systems added with [`AtlasLexer::add_system`] are ignored.
+                    /// The DFA is compiled the first time it's needed, so the first call is slower.
+                    pub fn tokenize_dfa(&mut self) -> Result<Vec<Token>, Diagnostic> {
+                        // The DFA doesn't tell how far it read for each token, so `relex` will start over
+                        self.lookahead.clear();
+                        let tok = self
+                            .tokenize_borrowed()?
+                            .iter()
+                            .map(|t| {
+                                t.to_token()
+                                    .ok_or_else(|| $crate::lexer::error::invalid_literal(t.span, t.text()))
+                            })
+                            .collect::<Result<Vec<Token>, Diagnostic>>()?;
+                        self.current_pos = BytePos::from(self.source.len());
+                        Ok(tok)
+                    }
+
+                    /// Tokenizes the source like [`AtlasLexer::tokenize_dfa`], but without copying anything:
+                    /// the identifiers, strings and numbers of the tokens are slices of the source.
+                    ///
+                    /// It's meant for one-shot tooling (e.g. syntax highlighting) where interning every
+                    /// identifier would be wasted, see [`BorrowedToken::intern`] to intern them on demand.
+                    pub fn tokenize_borrowed(&self) -> Result<Vec<BorrowedToken<'_>>, Diagnostic> {
+                        let vocabulary = default_vocabulary();
+                        let mut current_pos = self.current_pos;
+                        let mut tok: Vec<BorrowedToken> = vec![];
+                        tok.push(BorrowedToken::new(
+                            Span {
+                                start: current_pos,
+                                end: current_pos,
+                                file: self.file,
+                                expansion: ExpansionId::ROOT,
+                            },
+                            BorrowedTokenKind::SoI,
+                            "",
+                        ));
+                        while usize::from(current_pos) < self.source.len() {
+                            let start = current_pos;
+                            let rest = &self.source[usize::from(start)..];
+                            let (kind, len) = vocabulary.next_token(rest).ok_or_else(|| self.no_token(start))?;
+                            current_pos = start.shift_by(len);
+                            let kind = kind(&rest[..len]);
+                            if $allow_whitespace || !matches!(
+                                kind,
+                                BorrowedTokenKind::WhiteSpace
+                                    | BorrowedTokenKind::CarriageReturn
+                                    | BorrowedTokenKind::NewLine
+                                    | BorrowedTokenKind::Tabulation
+                            ) {
+                                tok.push(BorrowedToken::new(
+                                    Span {
+                                        start,
+                                        end: current_pos,
+                                        file: self.file,
+                                        expansion: ExpansionId::ROOT,
+                                    },
+                                    kind,
+                                    &rest[..len],
+                                ));
+                            }
                         }
-                    } else {
-                        break;
+                        tok.push(BorrowedToken::new(
+                            Span {
+                                start: current_pos,
+                                end: current_pos,
+                                file: self.file,
+                                expansion: ExpansionId::ROOT,
+                            },
+                            BorrowedTokenKind::EoI,
+                            "",
+                        ));
+                        Ok(tok)
                     }
                 }
-                if let Some(&'.') = state.peek() {
-                        n.push('.');
+                /// Builds the kind of a [`BorrowedToken`] from the text it matched
+                pub type BorrowedKindFn = for<'src> fn(&'src str) -> BorrowedTokenKind<'src>;
+                /// Compiles the whole declared vocabulary into a single DFA, the first time it's needed.
+                ///
+                /// Entries are added in the same order as the default systems so ties are resolved the same way.
+                /// The `Patterns` aren't compiled again, the set of [`default_pattern_set`] is shared.
+                pub fn default_vocabulary() -> &'static Vocabulary<BorrowedKindFn> {
+                    static VOCABULARY: std::sync::OnceLock<Vocabulary<BorrowedKindFn>> = std::sync::OnceLock::new();
+                    VOCABULARY.get_or_init(|| {
+                        let mut v = Vocabulary::<BorrowedKindFn>::builder();
+                        if $number {
+                            // Like the number system, an integer which doesn't fit is left to the other entries
+                            v.regex($crate::lexer::dfa::INT, Rule::WithText(|s| {
+                                let int: BorrowedKindFn = |s| BorrowedTokenKind::Literal(BorrowedLiteral::Int(s));
+                                s.parse::<i64>().is_ok().then_some(int)
+                            }));
+                            v.regex($crate::lexer::dfa::FLOAT, Rule::Token(|s| BorrowedTokenKind::Literal(BorrowedLiteral::Float(s))));
+                        }
+                        if $symbol {
+                            $(v.literal(&String::from($sym), Rule::Token(|_| BorrowedTokenKind::$variant));)*
+                            $(
+                                v.literal(&String::from_iter([$sym2, $sym3]), Rule::Token(|_| BorrowedTokenKind::$variant1));
+                                v.literal(&String::from($sym2), Rule::Token(|_| BorrowedTokenKind::$variant2));
+                            )*
+                        }
+                        if $keyword {
+                            $(v.literal($x, Rule::Token(|s| BorrowedTokenKind::Keyword(s)));)*
+                            v.regex($crate::lexer::dfa::IDENT, Rule::Token(|s| BorrowedTokenKind::Literal(BorrowedLiteral::Identifier(s))));
+                        }
+                        if $whitespace {
+                            v.literal(" ", Rule::Token(|_| BorrowedTokenKind::WhiteSpace));
+                            v.literal("\t", Rule::Token(|_| BorrowedTokenKind::Tabulation));
+                            v.literal("\n", Rule::Token(|_| BorrowedTokenKind::NewLine));
+                            v.literal("\r", Rule::Token(|_| BorrowedTokenKind::CarriageReturn));
+                        }
+                        if $string {
+                            v.regex($crate::lexer::dfa::STRING, Rule::Token(|s| {
+                                BorrowedTokenKind::Literal(BorrowedLiteral::StringLiteral(&s[1..s.len() - 1]))
+                            }));
+                        }
+                        v.compiled(
+                            default_pattern_set(),
+                            vec![$($(Rule::Token(|s| BorrowedTokenKind::$pattern_variant(s))),*)?],
+                        );
+                        v.build()
+                    })
+                }
+                pub fn default_number(c: char, state: &mut LexerState) -> Option<Token> {
+                    if c.is_ascii_digit() {
+                        let start = state.current_pos;
+                        let mut is_float = false;
+                        let mut n = String::new();
+                        n.push(c);
                         state.next();
-                        is_float = true;
                         loop {
                             if let Some(c) = state.peek() {
-                                if c.is_numeric() {
+                                if c.is_ascii_digit() {
                                     n.push(*c);
                                     state.next();
                                 } else {
@@ -186,68 +534,120 @@ macro_rules! lexer_builder {
                                 break;
                             }
                         }
-                }
-
-                Some(Token::new(
-                    Span {
-                        start,
-                        end: state.current_pos,
-                        path: state.path,
-                    },
-                    TokenKind::Literal(if is_float {Literal::Float(n.parse::<f64>().unwrap())} else {Literal::Int(n.parse::<i64>().unwrap())})),
-                )
-            } else {
-                None
-            }
-        }
-        pub fn default_whitespace(c: char, state: &mut LexerState) -> Option<Token> {
-            let start = state.current_pos;
-            let tok = match c {
-                ' ' => TokenKind::WhiteSpace,
-                '\t' => TokenKind::Tabulation,
-                '\n' => TokenKind::NewLine,
-                '\r' => TokenKind::CarriageReturn,
-                _ => return None,
-            };
-            state.next();
-            return Some(Token::new(
-                Span {
-                    start,
-                    end: state.current_pos,
-                    path: state.path,
-                },
-                tok,
-            ))
-        }
-        pub fn default_string(c: char, state: &mut LexerState) -> Option<Token> {
-            let start = state.current_pos;
-            let mut s = String::new();
-            if c == '"' {
-                println!("string in the making");
-                state.next();
-                loop {
-                    if let Some(ch) = state.peek() {
-                        if *ch == '"' {
-                            state.next();
-                            break;
+                        if let Some(&'.') = state.peek() {
+                                n.push('.');
+                                state.next();
+                                is_float = true;
+                                loop {
+                                    if let Some(c) = state.peek() {
+                                        if c.is_ascii_digit() {
+                                            n.push(*c);
+                                            state.next();
+                                        } else {
+                                            break;
+                                        }
+                                    } else {
+                                        break;
+                                    }
+                                }
                         }
-                        s.push(*ch);
+
+                        Some(Token::new(
+                            Span {
+                                start,
+                                end: state.current_pos,
+                                file: state.file,
+                                expansion: ExpansionId::ROOT,
+                            },
+                            // A number that doesn't fit is left to the lexer, which reports it
+                            TokenKind::Literal(if is_float {Literal::Float(n.parse::<f64>().ok()?)} else {Literal::Int(n.parse::<i64>().ok()?)})),
+                        )
+                    } else {
+                        None
+                    }
+                }
+                pub fn default_whitespace(c: char, state: &mut LexerState) -> Option<Token> {
+                    let start = state.current_pos;
+                    let tok = match c {
+                        ' ' => TokenKind::WhiteSpace,
+                        '\t' => TokenKind::Tabulation,
+                        '\n' => TokenKind::NewLine,
+                        '\r' => TokenKind::CarriageReturn,
+                        _ => return None,
+                    };
+                    state.next();
+                    return Some(Token::new(
+                        Span {
+                            start,
+                            end: state.current_pos,
+                            file: state.file,
+                            expansion: ExpansionId::ROOT,
+                        },
+                        tok,
+                    ))
+                }
+                pub fn default_string(c: char, state: &mut LexerState) -> Option<Token> {
+                    let start = state.current_pos;
+                    let mut s = String::new();
+                    if c == '"' {
                         state.next();
+                        loop {
+                            if let Some(ch) = state.peek() {
+                                if *ch == '"' {
+                                    state.next();
+                                    break;
+                                }
+                                s.push(*ch);
+                                state.next();
+                            } else {
+                                // Unterminated string
+                                return None;
+                            }
+                        }
+                        return Some(Token::new(
+                            Span {
+                                start,
+                                end: state.current_pos,
+                                file: state.file,
+                                expansion: ExpansionId::ROOT,
+                            },
+                            TokenKind::Literal(Literal::StringLiteral(Intern::new(s))),
+                        ));
+                    } else {
+                        None
                     }
                 }
-                return Some(Token::new(
-                    Span {
-                        start,
-                        end: state.current_pos,
-                        path: state.path,
-                    },
-                    TokenKind::Literal(Literal::StringLiteral(Intern::new(s))),
-                ));
-            } else {
-                None
+                pub const PATTERNS: &[&str] = &[$($($pattern),*)?];
+                /// Loads the `Patterns` compiled by `lexer_builder!`, shared by [`default_pattern`] and
+                /// [`default_vocabulary`], the first time they're needed.
+                pub fn default_pattern_set() -> &'static PatternSet {
+                    static SET: std::sync::OnceLock<PatternSet> = std::sync::OnceLock::new();
+                    SET.get_or_init(|| {
+                        PatternSet::from_bytes(&PATTERNS_DFA.bytes)
+                            .expect("the DFA of the `Patterns` compiled by `lexer_builder!` can't be loaded")
+                    })
+                }
+                pub fn default_pattern(_: char, state: &mut LexerState) -> Option<Token> {
+                    const KINDS: &[fn(Intern<String>) -> TokenKind] = &[$($(TokenKind::$pattern_variant),*)?];
+                    let set = default_pattern_set();
+                    let start = state.current_pos;
+                    let (found, read) = set.longest_match_lookahead(state.remaining());
+                    state.look_ahead(read);
+                    let (idx, len) = found?;
+                    let text = Intern::new(state.remaining()[..len].to_owned());
+                    state.skip_bytes(len);
+                    Some(Token::new(
+                        Span {
+                            start,
+                            end: state.current_pos,
+                            file: state.file,
+                            expansion: ExpansionId::ROOT,
+                        },
+                        KINDS[idx](text),
+                    ))
+                }
             }
         }
-
     };
 }
 
@@ -260,11 +660,15 @@ macro_rules! tokens {
         }, Either {
             $($sym2:literal =>  $sym3:literal => $variant2:ident, $variant3:ident ),* $(,)?
         }
-    }, Number {$($trail_enum:ident($trail_type:ty)),+ $(,)?}) => {
-        #[derive(Debug, Clone, Copy, PartialEq)]
-        pub struct Token {
-            span: Span,
-            kind: TokenKind,
+    }, Number {$($trail_enum:ident($trail_type:ty)),+ $(,)?}
+    $(, Patterns {$($pattern_variant:ident),* $(,)?})? $(,)?) => {
+        $crate::__serde_import!();
+        $crate::__serde_item! {
+            #[derive(Debug, Clone, Copy, PartialEq)]
+            pub struct Token {
+                span: Span,
+                kind: TokenKind,
+            }
         }
 
         impl Spanned for Token {
@@ -274,6 +678,21 @@ macro_rules! tokens {
             }
         }
 
+        impl $crate::utils::naming::NameToken for Token {
+            fn name_token(&self) -> $crate::utils::naming::NameTokenKind<'_> {
+                use $crate::utils::naming::NameTokenKind;
+                match &self.kind {
+                    TokenKind::Keyword(k) => NameTokenKind::Keyword(k.as_str()),
+                    TokenKind::Literal(Literal::Identifier(i)) => NameTokenKind::Identifier(i.as_str()),
+                    TokenKind::WhiteSpace
+                    | TokenKind::NewLine
+                    | TokenKind::Tabulation
+                    | TokenKind::CarriageReturn => NameTokenKind::Trivia,
+                    _ => NameTokenKind::Other,
+                }
+            }
+        }
+
         impl Token {
             pub const fn new(span: Span, kind: TokenKind) -> Self {
                 Self { span, kind }
@@ -282,33 +701,629 @@ macro_rules! tokens {
             pub fn kind(&self) -> TokenKind {
                 self.kind
             }
+            /// Returns the text of the token in `source`, the source it was lexed from.
+            ///
+            /// Fails if the span of the token is out of the bounds of `source` or not on char boundaries.
+            pub fn text<'src>(&self, source: &'src str) -> Result<&'src str, SpanError> {
+                self.span.slice(source)
+            }
         }
 
-        #[derive(Debug, Clone, Copy, PartialEq)]
-        pub enum Literal {
-            ///Default int literal, may change in the parser based on the type of the variable
+        $crate::__serde_item! {
+            #[derive(Debug, Clone, Copy, PartialEq)]
+            #[allow(clippy::enum_variant_names)]
+            pub enum Literal {
+                ///Default int literal, may change in the parser based on the type of the variable
+
+                Int(i64),
+                $(
+                    $trail_enum($trail_type),
+                )+
+                ///Default float literal, may change in the parser based on the type of the variable
+                Float(f64),
+
+                Bool(bool),
+                //At this point, types don't exist in the parser, it's just Identifier
+                Identifier(Intern<String>),
+
+                StringLiteral(Intern<String>),
+            }
+        }
+
+        $crate::__serde_item! {
+            #[derive(Debug, Clone, Copy, PartialEq)]
+            pub enum TokenKind {
+                /// A literal see [Literal] for more information
+                Literal(Literal),
+
+                /// A keyword
+                Keyword(Intern<String>),
+                $(
+                    $variant,
+                )*
+                $(
+                    $variant2,
+                    $variant3,
+                )*
+                $($(
+                    /// A token matched by one of the `Patterns`, holding the matched text
+                    $pattern_variant(Intern<String>),
+                )*)?
+                WhiteSpace,
+                NewLine,
+                Tabulation,
+                CarriageReturn,
+                EoI,
+                SoI
+            }
+        }
 
-            Int(i64),
+        /// The kind of a token without its payload, see [`TokenBuffer`]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum TokenTag {
+            /// Any literal other than an identifier or a string
+            Literal,
+            Identifier,
+            StringLiteral,
+            Keyword,
             $(
-                $trail_enum($trail_type),
-            )+
-            ///Default float literal, may change in the parser based on the type of the variable
-            Float(f64),
+                $variant,
+            )*
+            $(
+                $variant2,
+                $variant3,
+            )*
+            $($(
+                $pattern_variant,
+            )*)?
+            WhiteSpace,
+            NewLine,
+            Tabulation,
+            CarriageReturn,
+            EoI,
+            SoI
+        }
+
+        impl TokenKind {
+            /// Returns the kind without its payload
+            pub fn tag(&self) -> TokenTag {
+                match self {
+                    TokenKind::Literal(Literal::Identifier(_)) => TokenTag::Identifier,
+                    TokenKind::Literal(Literal::StringLiteral(_)) => TokenTag::StringLiteral,
+                    TokenKind::Literal(_) => TokenTag::Literal,
+                    TokenKind::Keyword(_) => TokenTag::Keyword,
+                    $(
+                        TokenKind::$variant => TokenTag::$variant,
+                    )*
+                    $(
+                        TokenKind::$variant2 => TokenTag::$variant2,
+                        TokenKind::$variant3 => TokenTag::$variant3,
+                    )*
+                    $($(
+                        TokenKind::$pattern_variant(_) => TokenTag::$pattern_variant,
+                    )*)?
+                    TokenKind::WhiteSpace => TokenTag::WhiteSpace,
+                    TokenKind::NewLine => TokenTag::NewLine,
+                    TokenKind::Tabulation => TokenTag::Tabulation,
+                    TokenKind::CarriageReturn => TokenTag::CarriageReturn,
+                    TokenKind::EoI => TokenTag::EoI,
+                    TokenKind::SoI => TokenTag::SoI,
+                }
+            }
+        }
+
+        impl TokenTag {
+            /// Returns the `TokenKind` of this tag, `None` if the kind needs a payload
+            pub fn to_kind(self) -> Option<TokenKind> {
+                Some(match self {
+                    TokenTag::Literal
+                    | TokenTag::Identifier
+                    | TokenTag::StringLiteral
+                    | TokenTag::Keyword => return None,
+                    $(
+                        TokenTag::$variant => TokenKind::$variant,
+                    )*
+                    $(
+                        TokenTag::$variant2 => TokenKind::$variant2,
+                        TokenTag::$variant3 => TokenKind::$variant3,
+                    )*
+                    $($(
+                        TokenTag::$pattern_variant => return None,
+                    )*)?
+                    TokenTag::WhiteSpace => TokenKind::WhiteSpace,
+                    TokenTag::NewLine => TokenKind::NewLine,
+                    TokenTag::Tabulation => TokenKind::Tabulation,
+                    TokenTag::CarriageReturn => TokenKind::CarriageReturn,
+                    TokenTag::EoI => TokenKind::EoI,
+                    TokenTag::SoI => TokenKind::SoI,
+                })
+            }
+        }
+
+        /// Names the tag for the users, like "`;`" or "an identifier"
+        impl ::core::fmt::Display for TokenTag {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                f.write_str(match self {
+                    TokenTag::Literal => "a literal",
+                    TokenTag::Identifier => "an identifier",
+                    TokenTag::StringLiteral => "a string",
+                    TokenTag::Keyword => "a keyword",
+                    $(
+                        TokenTag::$variant => concat!("`", $sym, "`"),
+                    )*
+                    $(
+                        TokenTag::$variant2 => concat!("`", $sym2, $sym3, "`"),
+                        TokenTag::$variant3 => concat!("`", $sym2, "`"),
+                    )*
+                    $($(
+                        TokenTag::$pattern_variant => concat!("a ", stringify!($pattern_variant)),
+                    )*)?
+                    TokenTag::WhiteSpace => "a whitespace",
+                    TokenTag::NewLine => "a line ending",
+                    TokenTag::Tabulation => "a tabulation",
+                    TokenTag::CarriageReturn => "a carriage return",
+                    TokenTag::EoI => "the end of the input",
+                    TokenTag::SoI => "the start of the input",
+                })
+            }
+        }
+
+        /// Names the token for the users with its payload, like "keyword `let`" or "`42`"
+        impl ::core::fmt::Display for TokenKind {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                match self {
+                    TokenKind::Literal(Literal::Int(i)) => write!(f, "`{}`", i),
+                    $(
+                        TokenKind::Literal(Literal::$trail_enum(n)) => write!(f, "`{:?}`", n),
+                    )+
+                    TokenKind::Literal(Literal::Float(n)) => write!(f, "`{:?}`", n),
+                    TokenKind::Literal(Literal::Bool(b)) => write!(f, "`{}`", b),
+                    TokenKind::Literal(Literal::Identifier(s)) => write!(f, "identifier `{}`", s),
+                    TokenKind::Literal(Literal::StringLiteral(s)) => write!(f, "string {:?}", s.as_str()),
+                    TokenKind::Keyword(k) => write!(f, "keyword `{}`", k),
+                    $($(
+                        TokenKind::$pattern_variant(s) => write!(f, "{} `{}`", stringify!($pattern_variant), s),
+                    )*)?
+                    _ => write!(f, "{}", self.tag()),
+                }
+            }
+        }
+
+        /// A compact, struct-of-arrays storage for the tokens of a single file.
+        ///
+        /// Instead of a full [`Token`] per entry, it only keeps the tag, the start offset and the length
+        /// of each token. The payloads of literals, keywords and patterns go in a side table, so
+        /// whole-project indexing takes several times less memory than a `Vec<Token>`.
+        #[derive(Debug, Clone, Default, PartialEq)]
+        pub struct TokenBuffer {
+            file: FileId,
+            tags: Vec<TokenTag>,
+            starts: Vec<u32>,
+            lens: Vec<u32>,
+            /// Indices of the tokens whose payload is an interned string, sorted
+            interned_owners: Vec<u32>,
+            interned: Vec<Intern<String>>,
+            /// Indices of the other literals, sorted
+            literal_owners: Vec<u32>,
+            literals: Vec<Literal>,
+        }
+
+        impl TokenBuffer {
+            /// Creates an empty buffer for the tokens of `file`
+            pub fn new(file: FileId) -> Self {
+                Self {
+                    file,
+                    ..Default::default()
+                }
+            }
+
+            /// Appends a token to the buffer.
+            ///
+            /// # Panics
+            /// Panics if the token comes from another file than the previous ones,
+            /// or if it lies beyond the first 4GiB of the file.
+            pub fn push(&mut self, token: Token) {
+                if self.tags.is_empty() {
+                    self.file = token.span.file;
+                }
+                assert_eq!(self.file, token.span.file, "A TokenBuffer can only hold the tokens of a single file");
+                let start = u32::try_from(usize::from(token.span.start)).expect("TokenBuffer only supports files up to 4GiB");
+                let end = u32::try_from(usize::from(token.span.end)).expect("TokenBuffer only supports files up to 4GiB");
+                let index = self.tags.len() as u32;
+                match token.kind {
+                    TokenKind::Literal(Literal::Identifier(s))
+                    | TokenKind::Literal(Literal::StringLiteral(s))
+                    | TokenKind::Keyword(s) => {
+                        self.interned_owners.push(index);
+                        self.interned.push(s);
+                    }
+                    $($(
+                        TokenKind::$pattern_variant(s) => {
+                            self.interned_owners.push(index);
+                            self.interned.push(s);
+                        }
+                    )*)?
+                    TokenKind::Literal(l) => {
+                        self.literal_owners.push(index);
+                        self.literals.push(l);
+                    }
+                    _ => {}
+                }
+                let tag = token.kind.tag();
+                self.tags.push(tag);
+                self.starts.push(start);
+                self.lens.push(end - start);
+            }
+
+            /// Returns the number of tokens in the buffer
+            pub fn len(&self) -> usize {
+                self.tags.len()
+            }
+
+            /// Returns `true` if the buffer doesn't hold any token
+            pub fn is_empty(&self) -> bool {
+                self.tags.is_empty()
+            }
+
+            /// Returns the tag of the token at `index`
+            pub fn tag(&self, index: usize) -> Option<TokenTag> {
+                self.tags.get(index).copied()
+            }
+
+            /// Returns the span of the token at `index`
+            pub fn span(&self, index: usize) -> Option<Span> {
+                let start = BytePos::from(*self.starts.get(index)? as usize);
+                Some(Span {
+                    start,
+                    end: start.shift_by(self.lens[index] as usize),
+                    file: self.file,
+                    expansion: ExpansionId::ROOT,
+                })
+            }
+
+            /// Returns the kind of the token at `index`, looking up its payload if needed
+            pub fn kind(&self, index: usize) -> Option<TokenKind> {
+                let tag = self.tag(index)?;
+                if let Some(kind) = tag.to_kind() {
+                    return Some(kind);
+                }
+                if tag == TokenTag::Literal {
+                    let i = self.literal_owners.binary_search(&(index as u32)).ok()?;
+                    return Some(TokenKind::Literal(self.literals[i]));
+                }
+                let i = self.interned_owners.binary_search(&(index as u32)).ok()?;
+                let s = self.interned[i];
+                Some(match tag {
+                    TokenTag::Identifier => TokenKind::Literal(Literal::Identifier(s)),
+                    TokenTag::StringLiteral => TokenKind::Literal(Literal::StringLiteral(s)),
+                    $($(
+                        TokenTag::$pattern_variant => TokenKind::$pattern_variant(s),
+                    )*)?
+                    _ => TokenKind::Keyword(s),
+                })
+            }
+
+            /// Rebuilds the token at `index`
+            pub fn get(&self, index: usize) -> Option<Token> {
+                Some(Token::new(self.span(index)?, self.kind(index)?))
+            }
+
+            /// Returns a cursor over the tokens, starting at the first one
+            pub fn cursor(&self) -> TokenCursor<'_> {
+                TokenCursor {
+                    buffer: self,
+                    index: 0,
+                }
+            }
+
+            /// Returns the number of bytes allocated by the buffer
+            pub fn heap_size(&self) -> usize {
+                self.tags.capacity() * std::mem::size_of::<TokenTag>()
+                    + self.starts.capacity() * std::mem::size_of::<u32>()
+                    + self.lens.capacity() * std::mem::size_of::<u32>()
+                    + self.interned_owners.capacity() * std::mem::size_of::<u32>()
+                    + self.interned.capacity() * std::mem::size_of::<Intern<String>>()
+                    + self.literal_owners.capacity() * std::mem::size_of::<u32>()
+                    + self.literals.capacity() * std::mem::size_of::<Literal>()
+            }
+
+            /// Shrinks the capacity of the buffer as much as possible
+            pub fn shrink_to_fit(&mut self) {
+                self.tags.shrink_to_fit();
+                self.starts.shrink_to_fit();
+                self.lens.shrink_to_fit();
+                self.interned_owners.shrink_to_fit();
+                self.interned.shrink_to_fit();
+                self.literal_owners.shrink_to_fit();
+                self.literals.shrink_to_fit();
+            }
+        }
+
+        impl FromIterator<Token> for TokenBuffer {
+            fn from_iter<I: IntoIterator<Item = Token>>(iter: I) -> Self {
+                let mut buffer = TokenBuffer::default();
+                buffer.extend(iter);
+                buffer.shrink_to_fit();
+                buffer
+            }
+        }
+
+        impl Extend<Token> for TokenBuffer {
+            fn extend<I: IntoIterator<Item = Token>>(&mut self, iter: I) {
+                for token in iter {
+                    self.push(token);
+                }
+            }
+        }
+
+        /// A cursor walking through a [`TokenBuffer`]
+        #[derive(Debug, Clone, Copy)]
+        pub struct TokenCursor<'buf> {
+            buffer: &'buf TokenBuffer,
+            index: usize,
+        }
+
+        impl TokenCursor<'_> {
+            /// Returns the index of the token the cursor points to
+            pub fn index(&self) -> usize {
+                self.index
+            }
+
+            /// Moves the cursor to the token at `index`
+            pub fn seek(&mut self, index: usize) {
+                self.index = index;
+            }
+
+            /// Returns the tag of the current token without rebuilding it
+            pub fn peek_tag(&self) -> Option<TokenTag> {
+                self.buffer.tag(self.index)
+            }
+
+            /// Returns the current token without advancing
+            pub fn peek(&self) -> Option<Token> {
+                self.buffer.get(self.index)
+            }
+
+            /// Returns the token `n` positions after the current one without advancing
+            pub fn peek_nth(&self, n: usize) -> Option<Token> {
+                self.buffer.get(self.index + n)
+            }
+        }
+
+        impl Iterator for TokenCursor<'_> {
+            type Item = Token;
+
+            fn next(&mut self) -> Option<Token> {
+                let token = self.buffer.get(self.index)?;
+                self.index += 1;
+                Some(token)
+            }
+        }
 
-            Bool(bool),
-            //At this point, types don't exist in the parser, it's just Identifier
-            Identifier(Intern<String>),
+        /// A cursor over the tokens of a source, for hand-written parsers.
+        ///
+        /// The trivia (whitespaces, line endings, `SoI` and `EoI`) are skipped: every method only
+        /// sees the other tokens, and the stream ends after the last of them. The tokens are matched
+        /// by their [`TokenTag`], ignoring their payload, except with the `_kind` methods.
+        #[derive(Debug, Clone, Default, PartialEq)]
+        pub struct TokenStream {
+            tokens: Vec<Token>,
+            /// Index of the current token, never a trivia
+            index: usize,
+        }
+
+        /// A position in a [`TokenStream`], to go back to with [`TokenStream::rewind`]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct TokenCheckpoint(usize);
+
+        impl TokenStream {
+            /// Creates a stream starting at the first token of `tokens` that isn't a trivia
+            pub fn new(tokens: Vec<Token>) -> Self {
+                let mut stream = Self { tokens, index: 0 };
+                stream.skip_trivia();
+                stream
+            }
+
+            /// Returns `true` for the tokens skipped by the stream
+            fn is_trivia(token: &Token) -> bool {
+                matches!(
+                    token.kind,
+                    TokenKind::WhiteSpace
+                        | TokenKind::NewLine
+                        | TokenKind::Tabulation
+                        | TokenKind::CarriageReturn
+                        | TokenKind::SoI
+                        | TokenKind::EoI
+                )
+            }
+
+            fn skip_trivia(&mut self) {
+                while self.tokens.get(self.index).is_some_and(Self::is_trivia) {
+                    self.index += 1;
+                }
+            }
+
+            /// Returns the current token without advancing, `None` at the end of the stream
+            pub fn peek(&self) -> Option<Token> {
+                self.tokens.get(self.index).copied()
+            }
+
+            /// Returns the token `n` positions after the current one without advancing
+            /// (`peek_nth(0)` is `peek()`)
+            pub fn peek_nth(&self, n: usize) -> Option<Token> {
+                self.tokens
+                    .get(self.index..)
+                    .unwrap_or_default()
+                    .iter()
+                    .filter(|t| !Self::is_trivia(t))
+                    .nth(n)
+                    .copied()
+            }
+
+            /// Returns `true` if every token has been consumed
+            pub fn is_at_end(&self) -> bool {
+                self.index >= self.tokens.len()
+            }
+
+            /// Returns `true` if the current token is a `tag`, whatever its payload
+            pub fn at(&self, tag: TokenTag) -> bool {
+                self.peek().is_some_and(|t| t.kind.tag() == tag)
+            }
+
+            /// Returns `true` if the current token is exactly `kind`, payload included
+            /// (e.g. a given keyword)
+            pub fn at_kind(&self, kind: TokenKind) -> bool {
+                self.peek().is_some_and(|t| t.kind == kind)
+            }
+
+            /// Consumes and returns the current token, `None` at the end of the stream
+            pub fn bump(&mut self) -> Option<Token> {
+                let token = self.peek()?;
+                self.index += 1;
+                self.skip_trivia();
+                Some(token)
+            }
+
+            /// Consumes the current token if it's a `tag`
+            pub fn eat(&mut self, tag: TokenTag) -> Option<Token> {
+                if self.at(tag) {
+                    self.bump()
+                } else {
+                    None
+                }
+            }
+
+            /// Consumes the current token if it's exactly `kind`, payload included
+            pub fn eat_kind(&mut self, kind: TokenKind) -> Option<Token> {
+                if self.at_kind(kind) {
+                    self.bump()
+                } else {
+                    None
+                }
+            }
+
+            /// Consumes the current token if it's a `tag`, or returns an `A0005` `Diagnostic`
+            /// pointing at the token found instead (or at the end of the source) without advancing.
+            pub fn expect(&mut self, tag: TokenTag) -> Result<Token, Diagnostic> {
+                match self.eat(tag) {
+                    Some(token) => Ok(token),
+                    None => Err(self.unexpected(&tag)),
+                }
+            }
+
+            /// Consumes the current token if it's exactly `kind`, payload included, or returns an
+            /// `A0005` `Diagnostic` like [`TokenStream::expect`].
+            pub fn expect_kind(&mut self, kind: TokenKind) -> Result<Token, Diagnostic> {
+                match self.eat_kind(kind) {
+                    Some(token) => Ok(token),
+                    None => Err(self.unexpected(&kind)),
+                }
+            }
+
+            /// The diagnostic of a current token which isn't the `expected` one
+            fn unexpected(&self, expected: &dyn ::core::fmt::Display) -> Diagnostic {
+                let (found, label) = match self.peek() {
+                    Some(token) => (token.kind.to_string(), Label::primary(token.span, format!("expected {}", expected))),
+                    None => (TokenTag::EoI.to_string(), Label::primary(self.end_span(), format!("expected {} here", expected))),
+                };
+                Diagnostic::error(format!("expected {}, found {}", expected, found))
+                    .with_code($crate::utils::error_codes::A0005)
+                    .with_label(label)
+            }
+
+            /// Returns an empty span right after the last token that isn't a trivia
+            fn end_span(&self) -> Span {
+                let last = self.tokens.iter().rev().find(|t| !Self::is_trivia(t));
+                last.or(self.tokens.last()).map_or(Span::empty(), |t| Span {
+                    start: t.span.end,
+                    ..t.span
+                })
+            }
+
+            /// Returns the current position, to go back to with [`TokenStream::rewind`]
+            pub fn checkpoint(&self) -> TokenCheckpoint {
+                TokenCheckpoint(self.index)
+            }
+
+            /// Goes back (or forward) to `checkpoint`, taken on this stream.
+            ///
+            /// A checkpoint of another stream doesn't make it panic, but moves it to an arbitrary
+            /// position (the end if it's out of bounds).
+            pub fn rewind(&mut self, checkpoint: TokenCheckpoint) {
+                self.index = checkpoint.0.min(self.tokens.len());
+                self.skip_trivia();
+            }
+        }
+
+        impl From<Vec<Token>> for TokenStream {
+            fn from(tokens: Vec<Token>) -> Self {
+                Self::new(tokens)
+            }
+        }
+
+        impl Iterator for TokenStream {
+            type Item = Token;
+
+            fn next(&mut self) -> Option<Token> {
+                self.bump()
+            }
+        }
+
+        /// A token borrowing its text from the source, see `AtlasLexer::tokenize_borrowed`
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        pub struct BorrowedToken<'src> {
+            span: Span,
+            kind: BorrowedTokenKind<'src>,
+            text: &'src str,
+        }
+
+        impl Spanned for BorrowedToken<'_> {
+            #[inline(always)]
+            fn span(&self) -> Span {
+                self.span
+            }
+        }
 
-            StringLiteral(Intern<String>),
+        impl<'src> BorrowedToken<'src> {
+            pub const fn new(span: Span, kind: BorrowedTokenKind<'src>, text: &'src str) -> Self {
+                Self { span, kind, text }
+            }
+            #[inline(always)]
+            pub fn kind(&self) -> BorrowedTokenKind<'src> {
+                self.kind
+            }
+            /// The text of the source covered by the token
+            #[inline(always)]
+            pub fn text(&self) -> &'src str {
+                self.text
+            }
+            /// Interns the text of the token, only paying for it when it's actually needed
+            pub fn intern(&self) -> Intern<String> {
+                Intern::from_ref(self.text)
+            }
+            /// Converts it into an owned [`Token`], interning and parsing its payload.
+            ///
+            /// Returns `None` if a number doesn't fit in its literal type.
+            pub fn to_token(self) -> Option<Token> {
+                Some(Token::new(self.span, self.kind.to_token_kind()?))
+            }
         }
 
+        /// Same as [`Literal`], but holding slices of the source instead of parsed values
         #[derive(Debug, Clone, Copy, PartialEq)]
-        pub enum TokenKind {
-            /// A literal see [Literal] for more information
-            Literal(Literal),
+        #[allow(clippy::enum_variant_names)]
+        pub enum BorrowedLiteral<'src> {
+            Int(&'src str),
+            Float(&'src str),
+            Identifier(&'src str),
+            StringLiteral(&'src str),
+        }
 
-            /// A keyword
-            Keyword(Intern<String>),
+        /// Same as [`TokenKind`], but holding slices of the source instead of interned strings
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        pub enum BorrowedTokenKind<'src> {
+            Literal(BorrowedLiteral<'src>),
+            Keyword(&'src str),
             $(
                 $variant,
             )*
@@ -316,6 +1331,9 @@ macro_rules! tokens {
                 $variant2,
                 $variant3,
             )*
+            $($(
+                $pattern_variant(&'src str),
+            )*)?
             WhiteSpace,
             NewLine,
             Tabulation,
@@ -323,6 +1341,39 @@ macro_rules! tokens {
             EoI,
             SoI
         }
+
+        impl BorrowedTokenKind<'_> {
+            /// Converts it into an owned [`TokenKind`], interning and parsing its payload.
+            ///
+            /// Returns `None` if a number doesn't fit in its literal type.
+            pub fn to_token_kind(self) -> Option<TokenKind> {
+                Some(match self {
+                    BorrowedTokenKind::Literal(l) => TokenKind::Literal(match l {
+                        BorrowedLiteral::Int(s) => Literal::Int(s.parse().ok()?),
+                        BorrowedLiteral::Float(s) => Literal::Float(s.parse().ok()?),
+                        BorrowedLiteral::Identifier(s) => Literal::Identifier(Intern::from_ref(s)),
+                        BorrowedLiteral::StringLiteral(s) => Literal::StringLiteral(Intern::from_ref(s)),
+                    }),
+                    BorrowedTokenKind::Keyword(s) => TokenKind::Keyword(Intern::from_ref(s)),
+                    $(
+                        BorrowedTokenKind::$variant => TokenKind::$variant,
+                    )*
+                    $(
+                        BorrowedTokenKind::$variant2 => TokenKind::$variant2,
+                        BorrowedTokenKind::$variant3 => TokenKind::$variant3,
+                    )*
+                    $($(
+                        BorrowedTokenKind::$pattern_variant(s) => TokenKind::$pattern_variant(Intern::from_ref(s)),
+                    )*)?
+                    BorrowedTokenKind::WhiteSpace => TokenKind::WhiteSpace,
+                    BorrowedTokenKind::NewLine => TokenKind::NewLine,
+                    BorrowedTokenKind::Tabulation => TokenKind::Tabulation,
+                    BorrowedTokenKind::CarriageReturn => TokenKind::CarriageReturn,
+                    BorrowedTokenKind::EoI => TokenKind::EoI,
+                    BorrowedTokenKind::SoI => TokenKind::SoI,
+                })
+            }
+        }
         //TODO: add support for multi-char symbols
         fn default_symbol(c: char, state: &mut LexerState) -> Option<Token> {
             let start = state.current_pos;
@@ -356,7 +1407,8 @@ macro_rules! tokens {
                 Span {
                     start,
                     end: state.current_pos,
-                    path: state.path,
+                    file: state.file,
+                    expansion: ExpansionId::ROOT,
                 },
                 tok,
             ))
@@ -367,21 +1419,20 @@ macro_rules! tokens {
 #[macro_export]
 macro_rules! keywords {
     ($($x:literal),* $(,)?) => {
-        use std::collections::HashMap;
         pub fn default_keyword(c: char, state: &mut LexerState) -> Option<Token> {
             let start = state.current_pos;
             let mut s = String::new();
             if c.is_alphabetic() || c == '_' {
                 s.push(c);
                 state.next();
-                let keywords: HashMap<Intern<String>, TokenKind> = map! {
+                let keywords: ::std::collections::HashMap<Intern<String>, TokenKind> = map! {
                     $(
                         Intern::new(String::from($x)) => TokenKind::Keyword(Intern::new(String::from($x))),
                     )*
                 };
                 loop {
                     if let Some(c) = state.peek() {
-                        if c.is_alphabetic() || *c == '_' {
+                        if c.is_alphabetic() || c.is_numeric() || *c == '_' {
                             s.push(*c);
                             state.next();
                         } else {
@@ -395,13 +1446,15 @@ macro_rules! keywords {
                     Some(Token::new(Span {
                         start,
                         end: state.current_pos,
-                        path: state.path
+                        file: state.file,
+                        expansion: ExpansionId::ROOT,
                     }, *k))
                 } else {
                     return Some(Token::new(Span {
                         start,
                         end:state.current_pos,
-                        path: state.path
+                        file: state.file,
+                        expansion: ExpansionId::ROOT,
                     }, TokenKind::Literal(Literal::Identifier(Intern::new(s)))));
                 }
             } else {
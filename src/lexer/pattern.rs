@@ -0,0 +1,120 @@
+use regex_automata::{
+    dfa::{dense, Automaton, StartKind},
+    nfa::thompson,
+    util::syntax,
+    Anchored, Input, MatchKind,
+};
+
+/// `PatternSet` is a group of regular expressions compiled together into a single dense DFA.
+///
+/// It's used by the `Patterns` section of [`lexer_builder!`](crate::lexer_builder) to recognise
+/// token classes that are easier to describe with a regex (hex colours, durations like `10ms`, ...).
+/// There, the patterns are compiled when the lexer is built: an invalid pattern is a compile error,
+/// and the serialized DFA is only loaded with [`PatternSet::from_bytes`] at runtime.
+///
+/// Every pattern is matched anchored at the start of the input, and the longest match wins.
+/// If several patterns match the same length, the one declared first is picked.
+#[derive(Debug, Clone)]
+pub struct PatternSet {
+    dfa: dense::DFA<Vec<u32>>,
+}
+
+impl PatternSet {
+    /// Compiles every pattern into a single DFA.
+    ///
+    /// # Panics
+    /// Panics if one of the patterns isn't a valid regular expression, naming the faulty pattern.
+    pub fn new(patterns: &[&str]) -> Self {
+        for pattern in patterns {
+            if let Err(e) = syntax::parse(pattern) {
+                panic!("Invalid pattern `{}` in the lexer declaration: {}", pattern, e);
+            }
+        }
+        let dfa = dense::Builder::new()
+            .configure(
+                dense::Config::new()
+                    .match_kind(MatchKind::All)
+                    .start_kind(StartKind::Anchored),
+            )
+            .thompson(thompson::Config::new().shrink(true))
+            .build_many(patterns)
+            .expect("Unable to build the DFA for the lexer patterns");
+        Self { dfa }
+    }
+
+    /// Loads a set compiled ahead of time, from the bytes of a dense DFA serialized with the
+    /// endianness of the target (`DFA::to_bytes_little_endian` or `DFA::to_bytes_big_endian`).
+    ///
+    /// The bytes have to be aligned like a `u32`, see [`AlignedBytes`].
+    ///
+    /// # Returns
+    /// - `Some(set)`: The set of patterns.
+    /// - `None`: If the bytes aren't a valid DFA for this target, or aren't aligned.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (dfa, _) = dense::DFA::from_bytes(bytes).ok()?;
+        Some(Self { dfa: dfa.to_owned() })
+    }
+
+    /// Returns the number of patterns in the set.
+    pub fn len(&self) -> usize {
+        self.dfa.pattern_len()
+    }
+
+    /// Returns `true` if the set doesn't contain any pattern.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Finds the longest non-empty match at the very start of `input`.
+    ///
+    /// # Returns
+    /// - `Some((index, len))`: The index of the matching pattern and the length in bytes of the match.
+    /// - `None`: If no pattern matches the start of `input`.
+    pub fn longest_match(&self, input: &str) -> Option<(usize, usize)> {
+        self.longest_match_lookahead(input).0
+    }
+
+    /// Like [`PatternSet::longest_match`], but also returns how many bytes of `input` were read
+    /// before the search stopped, which can be more than the length of the match.
+    ///
+    /// It's `input.len() + 1` if the search reached the end of `input`.
+    pub fn longest_match_lookahead(&self, input: &str) -> (Option<(usize, usize)>, usize) {
+        let Ok(mut state) = self
+            .dfa
+            .start_state_forward(&Input::new(input).anchored(Anchored::Yes))
+        else {
+            return (None, 0);
+        };
+        let mut best = None;
+        for (i, b) in input.bytes().enumerate() {
+            state = self.dfa.next_state(state, b);
+            if self.dfa.is_match_state(state) {
+                // Matches are delayed by one byte, so this one ends right before `i`
+                best = self.best_pattern(state).map(|p| (p, i));
+            } else if self.dfa.is_dead_state(state) || self.dfa.is_quit_state(state) {
+                return (best.filter(|&(_, len)| len > 0), i + 1);
+            }
+        }
+        state = self.dfa.next_eoi_state(state);
+        if self.dfa.is_match_state(state) {
+            best = self.best_pattern(state).map(|p| (p, input.len()));
+        }
+        (best.filter(|&(_, len)| len > 0), input.len() + 1)
+    }
+
+    fn best_pattern(&self, state: regex_automata::util::primitives::StateID) -> Option<usize> {
+        (0..self.dfa.match_len(state))
+            .map(|i| self.dfa.match_pattern(state, i).as_usize())
+            .min()
+    }
+}
+
+/// Bytes aligned like a `u32`, as [`PatternSet::from_bytes`] needs them.
+///
+/// `lexer_builder!` embeds the DFA of the `Patterns` it compiles in a `static` of this type.
+#[doc(hidden)]
+#[repr(C)]
+pub struct AlignedBytes<B: ?Sized> {
+    pub align: [u32; 0],
+    pub bytes: B,
+}
@@ -0,0 +1,117 @@
+use std::{
+    any::Any,
+    cell::Cell,
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Arc, Mutex, OnceLock},
+    thread,
+};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+thread_local! {
+    /// `true` on the worker threads of a pool
+    static IS_WORKER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// A fixed set of worker threads, shared by every call to `tokenize_batch`.
+///
+/// The threads are started the first time the pool is used (one per available core) and wait
+/// for jobs for the rest of the program, so a batch doesn't pay for spawning them again.
+pub struct WorkerPool {
+    jobs: mpsc::Sender<Job>,
+    size: usize,
+}
+
+impl WorkerPool {
+    /// Returns the pool shared by the whole program, starting it if it's the first call.
+    pub fn global() -> &'static WorkerPool {
+        static POOL: OnceLock<WorkerPool> = OnceLock::new();
+        POOL.get_or_init(|| WorkerPool::new(thread::available_parallelism().map_or(1, |n| n.get())))
+    }
+
+    /// Starts a pool of `size` worker threads.
+    pub fn new(size: usize) -> Self {
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for i in 0..size.max(1) {
+            let receiver = Arc::clone(&receiver);
+            thread::Builder::new()
+                .name(format!("atlas-worker-{}", i))
+                .spawn(move || {
+                    IS_WORKER.with(|w| w.set(true));
+                    loop {
+                        // The lock is released as soon as a job is received, before running it
+                        let job = match receiver.lock() {
+                            Ok(receiver) => receiver.recv(),
+                            Err(_) => break,
+                        };
+                        match job {
+                            Ok(job) => job(),
+                            Err(_) => break,
+                        }
+                    }
+                })
+                .expect("failed to spawn a worker thread");
+        }
+        WorkerPool {
+            jobs,
+            size: size.max(1),
+        }
+    }
+
+    /// Returns the number of worker threads.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Calls `f` on every item from the worker threads, and returns the results in the order of `items`.
+    ///
+    /// A panic in `f` only fails its own item, with the panic message as the error.
+    ///
+    /// Called from a worker thread of any pool (e.g. `f` itself calls `map`), the items are processed
+    /// one after the other on the calling thread: waiting for the other workers could deadlock,
+    /// as they may all be waiting the same way.
+    pub fn map<T, R, F>(&self, items: Vec<T>, f: F) -> Vec<Result<R, String>>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        if IS_WORKER.with(Cell::get) {
+            return items
+                .into_iter()
+                .map(|item| panic::catch_unwind(AssertUnwindSafe(|| f(item))).map_err(panic_message))
+                .collect();
+        }
+        let f = Arc::new(f);
+        let (results, receiver) = mpsc::channel();
+        let len = items.len();
+        for (i, item) in items.into_iter().enumerate() {
+            let f = Arc::clone(&f);
+            let results = results.clone();
+            let job: Job = Box::new(move || {
+                let result = panic::catch_unwind(AssertUnwindSafe(|| f(item))).map_err(panic_message);
+                let _ = results.send((i, result));
+            });
+            self.jobs.send(job).expect("the worker threads never stop while the pool is alive");
+        }
+        drop(results);
+        let mut ordered: Vec<Option<Result<R, String>>> = (0..len).map(|_| None).collect();
+        for (i, result) in receiver {
+            ordered[i] = Some(result);
+        }
+        ordered
+            .into_iter()
+            .map(|result| result.unwrap_or_else(|| Err(String::from("the worker thread stopped"))))
+            .collect()
+    }
+}
+
+/// Returns the message given to `panic!`, if it's a string.
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| String::from("unknown panic"))
+}
@@ -18,22 +18,82 @@
 
 /// Contain a powerful macro to generate a fully fledge lexer tailored to the user needs
 pub mod lexer;
+// The tests live in `tests::tests`, only compiled with `cfg(test)`
+#[allow(clippy::module_inception)]
 mod tests;
 /// TODO
 pub mod utils;
 
+#[doc(hidden)]
+pub use atlas_core_macros::validate_lexer as __validate_lexer;
+
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub use serde as __serde;
+
+// The `serde` feature has to be checked here: a `cfg` in the output of `tokens!` would check the
+// features of the crate invoking it instead. `#[serde(crate = "..")]` only takes a string, which
+// can't be built from `$crate`, so `__serde_import!` brings serde in scope under a fixed name.
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __serde_import {
+    () => {
+        #[doc(hidden)]
+        use $crate::__serde as __atlas_core_serde;
+    };
+}
+
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __serde_item {
+    ($item:item) => {
+        #[derive($crate::__serde::Serialize, $crate::__serde::Deserialize)]
+        #[serde(crate = "__atlas_core_serde")]
+        $item
+    };
+}
+
+#[cfg(not(feature = "serde"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __serde_import {
+    () => {};
+}
+
+#[cfg(not(feature = "serde"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __serde_item {
+    ($item:item) => {
+        $item
+    };
+}
+
 #[doc = "Used to import the base set of features of this tool"]
 pub mod prelude {
     pub use crate::keywords;
     pub use crate::lexer;
+    pub use crate::lexer::dfa::{Rule, Vocabulary};
+    pub use crate::lexer::edit::TextEdit;
+    pub use crate::lexer::lexeme::{FromLexeme, Lexeme, Lexer};
     pub use crate::lexer::lexer_state::LexerState;
+    pub use crate::lexer::pattern::PatternSet;
     pub use crate::lexer_builder;
     pub use crate::map;
     pub use crate::tokens;
-    pub use crate::utils::{case::Case, span::*};
+    pub use crate::utils::{
+        case::{Case, CaseStyle},
+        diagnostic::{Diagnostic, Label, LabelStyle, Severity},
+        source_map::{FileId, SourceMap},
+        span::*,
+    };
+    pub use atlas_core_macros::{Lexer, Spanned};
     pub use internment::Intern;
 }
 
+/// Builds a `HashMap` from a list of `key => value` pairs
 #[macro_export]
 macro_rules! map {
     ($name:ident, &key: ty, &val: ty) => {
@@ -41,7 +101,7 @@ macro_rules! map {
     };
     ($($key:expr => $val:expr),* $(,)?) => {
         {
-            let mut map = HashMap::new();
+            let mut map = ::std::collections::HashMap::new();
             $(map.insert($key, $val);)*
             map
         }
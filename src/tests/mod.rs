@@ -1,6 +1,53 @@
 #[cfg(test)]
 mod tests {
+    /// The lexer shared by the tests which don't need a specific configuration
+    #[allow(dead_code)]
+    mod fixture {
+        use crate::prelude::*;
+
+        lexer_builder! {
+            DefaultSystem {
+                number: true,
+                symbol: true,
+                keyword: true,
+                string: true,
+                whitespace: {
+                    allow_them: false,
+                    use_system: true,
+                },
+            },
+            Symbols {
+                Single {
+                    '(' => LParen,
+                    ')' => RParen,
+                    '{' => LBrace,
+                    '}' => RBrace,
+                    '+' => OpAdd,
+                    ';' => Semicolon,
+                },
+                Either {
+                    '=' => '=' => OpEq, OpAssign,
+                }
+            },
+            Patterns {
+                r"#[0-9a-f]{6}" => Color,
+            },
+            Keyword {
+                "let", "if", "struct", "const", "fn",
+            },
+            Number {
+                trailing {
+                    "_i128" => i128 => I128,
+                },
+                float: true,
+                u_int: true,
+                int: true
+            },
+        }
+    }
+
     #[test]
+    #[allow(dead_code)]
     fn test_macros() {
         use crate::prelude::*;
 
@@ -30,7 +77,6 @@ mod tests {
                     '^' => OpPow,
                     '%' => OpMod,
                     '\\' => BackSlash,
-                    '_' => Underscore,
                     ';' => Semicolon,
                     '\'' => Quote,
                     '?' => Interrogation,
@@ -50,7 +96,7 @@ mod tests {
             },
             Keyword {
                 "then", "if", "else", "struct", "true", "false", "let", "import", "return", "enum", "List", "end", "do",
-                "i8", "i16", "i32", "int", "i128", "u8", "u16", "u32", "u64", "u128", "f32", "f64", "string", "List", "char",
+                "i8", "i16", "i32", "int", "i128", "u8", "u16", "u32", "u64", "u128", "f32", "f64", "string", "char",
             },
             Number {
                 trailing {
@@ -75,10 +121,1345 @@ mod tests {
         
         let mut lexer = AtlasLexer::default();
         lexer.source = String::from(":: - -> <= ~> : == !=");
-        lexer.path = "./test.atlas";
         let tokens = lexer.tokenize().unwrap();
         for token in tokens {
             println!("{:?}", token);
         }
     }
+
+    #[test]
+    fn test_token_buffer() {
+        use crate::prelude::*;
+        use self::fixture::*;
+
+        let mut map = SourceMap::new();
+        map.add("<stdin>", "");
+        let file = map.add("./test.atlas", "let x = (1 == 2.5); if (x) \"yes\";".repeat(100));
+        let mut lexer = AtlasLexer::default();
+        lexer
+            .set_source(String::from(map.source(file)))
+            .set_file(file);
+        let tokens = lexer.tokenize().unwrap();
+        let buffer: TokenBuffer = tokens.iter().copied().collect();
+
+        assert_eq!(buffer.len(), tokens.len());
+        assert_eq!(buffer.cursor().collect::<Vec<_>>(), tokens);
+        assert_eq!(buffer.get(4), Some(tokens[4]));
+        assert_eq!(buffer.tag(4), Some(TokenTag::LParen));
+
+        let mut cursor = buffer.cursor();
+        cursor.seek(1);
+        assert_eq!(cursor.peek_tag(), Some(TokenTag::Keyword));
+        assert_eq!(cursor.peek_nth(1).map(|t| t.kind()), Some(tokens[2].kind()));
+        assert_eq!(cursor.next(), Some(tokens[1]));
+        assert_eq!(cursor.index(), 2);
+
+        // Most tokens are symbols, which don't need anything else than their tag, start and length
+        assert!(buffer.heap_size() * 3 < tokens.len() * std::mem::size_of::<Token>());
+    }
+
+    #[test]
+    #[allow(dead_code)]
+    fn test_dfa_matches_systems() {
+        use crate::prelude::*;
+
+        lexer_builder! {
+            DefaultSystem {
+                number: true,
+                symbol: true,
+                keyword: true,
+                string: true,
+                whitespace: {
+                    allow_them: true,
+                    use_system: true,
+                },
+            },
+            Symbols {
+                Single {
+                    '(' => LParen,
+                    ')' => RParen,
+                    ';' => Semicolon,
+                },
+                Either {
+                    '=' => '=' => OpEq, OpAssign,
+                    '.' => '.' => DoubleDot, Dot,
+                    '-' => '>' => RArrow, OpSub,
+                }
+            },
+            Patterns {
+                r"[0-9]+ms" => Duration,
+                r"[0-9]{25,}" => BigInt,
+            },
+            Keyword {
+                "let", "if", "then", "else", "i8", "u64",
+            },
+            Number {
+                trailing {
+                    "_i8" => i8 => I8,
+                },
+                float: true,
+                u_int: true,
+                int: true
+            },
+        }
+
+        let source = "let x_1 = if (a == 1.5) then \"some string\" else 10ms;\r\n\tfoo.bar..baz -> _ - 2. i8 u64 i8x u6";
+        let mut lexer = AtlasLexer::default();
+        lexer.set_source(String::from(source));
+        let expected = lexer.tokenize().unwrap();
+        let mut lexer = AtlasLexer::default();
+        lexer.set_source(String::from(source));
+        assert_eq!(lexer.tokenize_dfa().unwrap(), expected);
+
+        let mut lexer = AtlasLexer::default();
+        lexer.set_source(String::from(source));
+        let borrowed = lexer.tokenize_borrowed().unwrap();
+        for (b, t) in borrowed.iter().zip(&expected) {
+            assert_eq!(b.to_token().as_ref(), Some(t));
+            assert_eq!(b.text(), &source[b.start()..b.end()]);
+        }
+        assert_eq!(borrowed[3].kind(), BorrowedTokenKind::Literal(BorrowedLiteral::Identifier("x_1")));
+        assert_eq!(borrowed[3].intern(), Intern::new(String::from("x_1")));
+        let last: Vec<BorrowedTokenKind> = borrowed.iter().rev().skip(1).step_by(2).take(4).map(|t| t.kind()).collect();
+        assert_eq!(
+            last,
+            [
+                BorrowedTokenKind::Literal(BorrowedLiteral::Identifier("u6")),
+                BorrowedTokenKind::Literal(BorrowedLiteral::Identifier("i8x")),
+                BorrowedTokenKind::Keyword("u64"),
+                BorrowedTokenKind::Keyword("i8"),
+            ]
+        );
+
+        // Both report the same diagnostic for a literal too large and a digit which isn't ASCII
+        use crate::utils::error_codes::{A0002, A0003};
+        for (source, code, at) in [("let n = 99999999999999999999;", A0003, 8..28), ("let h = ½;", A0002, 8..10)] {
+            let mut lexer = AtlasLexer::default();
+            lexer.set_source(String::from(source));
+            let diagnostic = lexer.tokenize().unwrap_err();
+            assert_eq!(diagnostic.code, Some(code), "{}", source);
+            let span = diagnostic.primary_span().unwrap();
+            assert_eq!(usize::from(span.start)..usize::from(span.end), at);
+            let mut lexer = AtlasLexer::default();
+            lexer.set_source(String::from(source));
+            assert_eq!(lexer.tokenize_dfa().unwrap_err(), diagnostic);
+            let mut lexer = AtlasLexer::default();
+            let tokens = lexer.set_source(String::from("let n = 9;")).tokenize().unwrap();
+            let edit = TextEdit::new(8..9, &source[at]);
+            assert_eq!(lexer.relex(&tokens, &edit).unwrap_err(), diagnostic);
+        }
+
+        // An integer too large for the number system is left to a pattern matching the same text
+        let big = "9".repeat(25);
+        let mut lexer = AtlasLexer::default();
+        let tokens = lexer.set_source(big.clone()).tokenize().unwrap();
+        assert_eq!(tokens[1].kind(), TokenKind::BigInt(Intern::new(big.clone())));
+        let mut lexer = AtlasLexer::default();
+        assert_eq!(lexer.set_source(big).tokenize_dfa().unwrap(), tokens);
+
+        // Both agree on random sources too, whether they're valid or not
+        let mut seed: u64 = 0x9E37_79B9_7F4A_7C15;
+        let mut rand = move |n: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % n as u64) as usize
+        };
+        const PIECES: [&str; 32] = [
+            "let", "if", "then", "i8", "u64", "i8x", "x_1", "é", "_", "1", "42", "1.5", "2.", "10ms", "m", "\"",
+            "\"str\"", "(", ")", ";", "=", "==", ".", "..", "-", "->", " ", "\t", "\r\n", "$", "99999999999999999999",
+            "9999999999999999999999999",
+        ];
+        for _ in 0..3000 {
+            let source: String = (0..1 + rand(8)).map(|_| PIECES[rand(PIECES.len())]).collect();
+            let mut lexer = AtlasLexer::default();
+            let expected = lexer.set_source(source.clone()).tokenize();
+            let mut lexer = AtlasLexer::default();
+            assert_eq!(lexer.set_source(source.clone()).tokenize_dfa(), expected, "{:?}", source);
+        }
+    }
+
+    #[test]
+    #[allow(dead_code)]
+    fn test_patterns() {
+        use crate::prelude::*;
+
+        lexer_builder! {
+            DefaultSystem {
+                number: true,
+                symbol: true,
+                keyword: true,
+                string: false,
+                whitespace: {
+                    allow_them: false,
+                    use_system: true,
+                },
+            },
+            Symbols {
+                Single {
+                    '#' => Hash,
+                },
+                Either {
+                    '=' => '=' => OpEq, OpAssign,
+                }
+            },
+            Patterns {
+                r"[0-9]+(ms|s)" => Duration,
+                r"#[0-9a-fA-F]{6}" => HexColour,
+                r"v[0-9]+\.[0-9]+\.[0-9]+" => Version,
+            },
+            Keyword {
+                "let",
+            },
+            Number {
+                trailing {
+                    "_i8" => i8 => I8,
+                },
+                float: true,
+                u_int: true,
+                int: true
+            },
+        }
+
+        let mut lexer = AtlasLexer::default();
+        lexer.set_source(String::from("let d = 10ms == 2 # #ff00AA v1.2.3 version"));
+        let kinds: Vec<TokenKind> = lexer
+            .tokenize()
+            .unwrap()
+            .into_iter()
+            .map(|t| t.kind())
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::SoI,
+                TokenKind::Keyword(Intern::new(String::from("let"))),
+                TokenKind::Literal(Literal::Identifier(Intern::new(String::from("d")))),
+                TokenKind::OpAssign,
+                TokenKind::Duration(Intern::new(String::from("10ms"))),
+                TokenKind::OpEq,
+                TokenKind::Literal(Literal::Int(2)),
+                TokenKind::Hash,
+                TokenKind::HexColour(Intern::new(String::from("#ff00AA"))),
+                TokenKind::Version(Intern::new(String::from("v1.2.3"))),
+                TokenKind::Literal(Literal::Identifier(Intern::new(String::from("version")))),
+                TokenKind::EoI,
+            ]
+        );
+        // Without strings, a `"` is only a character no token starts with
+        let mut lexer = AtlasLexer::default();
+        lexer.set_source(String::from("let s = \"text\""));
+        let diagnostic = lexer.tokenize().unwrap_err();
+        assert_eq!(diagnostic.code, Some(crate::utils::error_codes::A0002));
+        lexer.current_pos = BytePos::from(0);
+        assert_eq!(lexer.tokenize_dfa().unwrap_err(), diagnostic);
+
+        // The DFA serialized by `lexer_builder!` is loaded as the set `PatternSet::new` would compile
+        let compiled = PatternSet::new(PATTERNS);
+        assert_eq!(default_pattern_set().len(), PATTERNS.len());
+        for input in ["10ms", "2s", "10m", "#ff00AA", "#ff00", "v1.2.3", "v1.2", "v", "10"] {
+            assert_eq!(default_pattern_set().longest_match(input), compiled.longest_match(input), "{}", input);
+        }
+        // The vocabulary of `tokenize_dfa` shares that set
+        let (kind, len) = default_vocabulary().next_token("10ms == 2").unwrap();
+        assert_eq!((kind("10ms"), len), (BorrowedTokenKind::Duration("10ms"), 4));
+    }
+
+    #[test]
+    fn test_derive_lexer() {
+        use crate::prelude::*;
+
+        #[derive(Lexer, Debug, Clone, Copy, PartialEq)]
+        #[atlas_core(crate = "crate")]
+        enum Tok {
+            #[token("(")]
+            LParen,
+            #[token(")")]
+            RParen,
+            #[token("=")]
+            OpAssign,
+            #[token("==")]
+            OpEq,
+            #[token("->")]
+            RArrow,
+            #[keyword("let")]
+            Let,
+            #[keyword("if")]
+            If,
+            #[ident]
+            Ident(Intern<String>),
+            #[int]
+            Int(u8),
+            #[float]
+            Float(f64),
+            #[string]
+            Str(Intern<String>),
+            #[regex(r"[0-9]+ms")]
+            Duration(Intern<String>),
+            #[regex(r"//[^\n]*")]
+            #[skip]
+            Comment,
+            #[regex(r"[ \t\r\n]+")]
+            #[skip]
+            WhiteSpace,
+        }
+
+        let tokens = Tok::tokenize(FileId::default(), "let iffy = if (x == 2.5) -> \"str\" // comment\n10ms").unwrap();
+        let kinds: Vec<Tok> = tokens.iter().map(|t| t.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                Tok::Let,
+                Tok::Ident(Intern::new(String::from("iffy"))),
+                Tok::OpAssign,
+                Tok::If,
+                Tok::LParen,
+                Tok::Ident(Intern::new(String::from("x"))),
+                Tok::OpEq,
+                Tok::Float(2.5),
+                Tok::RParen,
+                Tok::RArrow,
+                Tok::Str(Intern::new(String::from("str"))),
+                Tok::Duration(Intern::new(String::from("10ms"))),
+            ]
+        );
+        assert_eq!(tokens[1].span.start, BytePos::from(4));
+        assert_eq!(tokens[1].span.end, BytePos::from(8));
+        // 300 doesn't fit in a `u8`
+        use crate::utils::error_codes::{A0001, A0002, A0003};
+        let code = |source| Tok::tokenize(FileId::default(), source).unwrap_err().code;
+        assert_eq!(code("300"), Some(A0003));
+        assert_eq!(code("let s = \"str"), Some(A0001));
+
+        #[derive(Lexer, Debug, Clone, Copy, PartialEq)]
+        #[atlas_core(crate = "crate")]
+        enum NoStrings {
+            #[ident]
+            Ident(Intern<String>),
+        }
+        assert_eq!((Tok::HAS_STRINGS, NoStrings::HAS_STRINGS), (true, false));
+        let diagnostic = NoStrings::tokenize(FileId::default(), "\"str").unwrap_err();
+        assert_eq!(diagnostic.code, Some(A0002));
+    }
+
+    #[test]
+    fn test_tokenize_batch() {
+        use crate::prelude::*;
+        use self::fixture::*;
+
+        let mut map = SourceMap::new();
+        for i in 0..64 {
+            map.add(
+                format!("{}.atlas", i),
+                format!("let v{} = (\"file {}\" + {}) == {};", "x".repeat(i), i, i, i * 2).repeat(i + 1),
+            );
+        }
+        let files: Vec<(FileId, String)> = map
+            .files()
+            .map(|(id, f)| (id, String::from(f.source())))
+            .collect();
+        let sequential: Vec<Result<Vec<Token>, Diagnostic>> = files
+            .iter()
+            .map(|(file, source)| {
+                let mut lexer = AtlasLexer::default();
+                lexer.set_file(*file).set_source(source.clone());
+                lexer.tokenize()
+            })
+            .collect();
+
+        let lexer = AtlasLexer::default();
+        let first = lexer.tokenize_batch(files.clone());
+        let second = lexer.tokenize_batch(files.clone());
+        assert_eq!(first, sequential);
+        assert_eq!(second, sequential);
+        // An unknown character only fails its own file
+        let ok = map.add("ok.atlas", "let x");
+        let err = map.add("err.atlas", "let $");
+        let results = lexer.tokenize_batch(vec![
+            (ok, String::from(map.source(ok))),
+            (err, String::from(map.source(err))),
+        ]);
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+        // A panic in a system only fails its own file too, and the workers keep running
+        let mut panicking = AtlasLexer::default();
+        panicking.add_system(|c, _| if c == '@' { panic!("`@` isn't supported") } else { None });
+        let panics = map.add("panic.atlas", "let @");
+        let results = panicking.tokenize_batch(vec![
+            (ok, String::from(map.source(ok))),
+            (panics, String::from(map.source(panics))),
+        ]);
+        assert!(results[0].is_ok());
+        let diagnostic = results[1].as_ref().unwrap_err();
+        assert_eq!(diagnostic.message, "the lexer panicked");
+        assert_eq!(diagnostic.notes, ["`@` isn't supported"]);
+        assert_eq!(diagnostic.primary_span().map(|s| s.file), Some(panics));
+        assert_eq!(lexer.tokenize_batch(files), sequential);
+
+        // A nested batch runs on the worker itself, a single worker would wait for itself otherwise
+        use crate::lexer::pool::WorkerPool;
+        let pool: &'static WorkerPool = Box::leak(Box::new(WorkerPool::new(1)));
+        let results = pool.map(vec![1, 2], move |n| pool.map(vec![n, n * 10], |m| m + 1));
+        assert_eq!(results, [Ok(vec![Ok(2), Ok(11)]), Ok(vec![Ok(3), Ok(21)])]);
+    }
+
+    #[test]
+    #[allow(dead_code)]
+    fn test_relex() {
+        use crate::prelude::*;
+
+        lexer_builder! {
+            DefaultSystem {
+                number: true,
+                symbol: true,
+                keyword: true,
+                string: true,
+                whitespace: {
+                    allow_them: false,
+                    use_system: true,
+                },
+            },
+            Symbols {
+                Single {
+                    '(' => LParen,
+                    ')' => RParen,
+                    '+' => OpAdd,
+                    ';' => Semicolon,
+                },
+                Either {
+                    '=' => '=' => OpEq, OpAssign,
+                    '.' => '.' => DoubleDot, Dot,
+                    '-' => '>' => RArrow, OpSub,
+                }
+            },
+            Patterns {
+                r"[0-9]+m-?[0-9]+s" => Duration,
+            },
+            Keyword {
+                "let", "if", "then",
+            },
+            Number {
+                trailing {
+                    "_i8" => i8 => I8,
+                },
+                float: true,
+                u_int: true,
+                int: true
+            },
+        }
+
+        // The pattern reads `1m-2` before giving up, so adding the `s` changes the first token
+        let mut lexer = AtlasLexer::default();
+        lexer.set_source(String::from("1m-2"));
+        let tokens = lexer.tokenize().unwrap();
+        assert_eq!(tokens.len(), 6);
+        let relexed = lexer.relex(&tokens, &TextEdit::new(4..4, "s")).unwrap();
+        assert_eq!(relexed[1].kind(), TokenKind::Duration(Intern::new(String::from("1m-2s"))));
+        assert_eq!(relexed.len(), 3);
+
+        // The tokens of another source, even as many, aren't reused
+        let mut other = AtlasLexer::default();
+        let foreign = other.set_source(String::from("let abc = 1;")).tokenize().unwrap();
+        let mut lexer = AtlasLexer::default();
+        lexer.set_source(String::from("let x = 123;")).tokenize().unwrap();
+        let relexed = lexer.relex(&foreign, &TextEdit::new(12..12, " x")).unwrap();
+        let mut expected = AtlasLexer::default();
+        assert_eq!(relexed, expected.set_source(String::from("let x = 123; x")).tokenize().unwrap());
+
+        // Small xorshift generator, so the test is reproducible without any dependency
+        let mut seed: u64 = 0x2545_F491_4F6C_DD1D;
+        let mut rand = move |n: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % n as u64) as usize
+        };
+        const PIECES: [&str; 25] = [
+            "let", "if", "then", "lett", "x", "é", "_", "1", "2.5", "3.", "\"", "\"str\"", "(", ")", "+", ";", "=",
+            "==", ".", "-", ">", " ", "m", "s", "1m-2",
+        ];
+        let piece = |rand: &mut dyn FnMut(usize) -> usize| -> String {
+            (0..rand(4)).map(|_| PIECES[rand(PIECES.len())]).collect::<Vec<_>>().join(if rand(2) == 0 { " " } else { "" })
+        };
+
+        let mut checked = 0;
+        while checked < 5000 {
+            let source: String = (0..rand(12)).map(|_| piece(&mut rand)).collect::<Vec<_>>().join(" ");
+            let mut lexer = AtlasLexer::default();
+            lexer.set_source(source.clone());
+            let Ok(mut tokens) = lexer.tokenize() else {
+                continue;
+            };
+            // Several edits in a row, each one relying on what the previous `relex` remembered
+            for _ in 0..3 {
+                let source = lexer.source.clone();
+                let boundaries: Vec<usize> = source.char_indices().map(|(i, _)| i).chain([source.len()]).collect();
+                let a = boundaries[rand(boundaries.len())];
+                let b = boundaries[rand(boundaries.len())];
+                let edit = TextEdit::new(a.min(b)..a.max(b), piece(&mut rand));
+
+                let mut edited = source.clone();
+                edit.apply(&mut edited);
+                let mut full = AtlasLexer::default();
+                full.set_source(edited.clone());
+                let expected = full.tokenize();
+
+                let result = lexer.relex(&tokens, &edit);
+                assert_eq!(lexer.source, edited);
+                assert_eq!(result, expected, "{:?} edited by {:?}", source, edit);
+                checked += 1;
+                let Ok(relexed) = result else {
+                    break;
+                };
+                tokens = relexed;
+            }
+        }
+    }
+
+    #[test]
+    fn test_line_info() {
+        use crate::prelude::*;
+
+        let mut map = SourceMap::new();
+        let file = map.add("<stdin>", "let é = 1;\nlet s = \"ünïcode\"\r\n\nend");
+        let span = |start: usize, end: usize| unsafe { Span::new_unchecked(start, end, file) };
+
+        // `é` is 2 bytes long but only counts for one column
+        let info = span(4, 6).get_line_info(&map).unwrap();
+        assert_eq!((info.start_line, info.start_column), (1, 5));
+        assert_eq!((info.end_line, info.end_column), (1, 6));
+        assert_eq!(info.line_text, "let é = 1;");
+
+        // The `;` right before the end of the line
+        let info = span(10, 11).get_line_info(&map).unwrap();
+        assert_eq!((info.start_line, info.start_column), (1, 10));
+        assert_eq!((info.end_line, info.end_column), (1, 11));
+
+        // An empty span at the start of an empty line
+        let info = span(33, 33).get_line_info(&map).unwrap();
+        assert_eq!((info.start_line, info.start_column), (3, 1));
+        assert_eq!(info.line_text, "");
+
+        // The string literal, then the whole file
+        let start = map.source(file).find('"').unwrap();
+        let end = map.source(file).rfind('"').unwrap() + 1;
+        let info = span(start, end).get_line_info(&map).unwrap();
+        assert_eq!((info.start_line, info.start_column), (2, 9));
+        assert_eq!((info.end_line, info.end_column), (2, 18));
+        assert_eq!(info.line_text, "let s = \"ünïcode\"");
+
+        let len = map.source(file).len();
+        let info = span(0, len).get_line_info(&map).unwrap();
+        assert_eq!((info.start_line, info.start_column), (1, 1));
+        assert_eq!((info.end_line, info.end_column), (4, 4));
+        assert_eq!(info.line_text, "let é = 1;\nlet s = \"ünïcode\"\n\nend");
+
+        // An empty span at the very end of the file
+        let info = span(len, len).get_line_info(&map).unwrap();
+        assert_eq!((info.start_line, info.start_column), (4, 4));
+        assert_eq!(info.line_text, "end");
+
+        // Spans which don't fit in their file are reported instead of panicking
+        assert_eq!(span(0, len + 1).get_line_info(&map), Err(SpanError::OutOfBounds { end: len + 1, len }));
+        assert_eq!(span(5, 6).get_line_info(&map), Err(SpanError::NotCharBoundary(5)));
+        assert_eq!(span(6, 4).get_line_info(&map), Err(SpanError::Inverted { start: 6, end: 4 }));
+        let unknown = unsafe { Span::new_unchecked(0, 0, FileId::default()) };
+        assert_eq!(unknown.get_line_info(&SourceMap::new()), Err(SpanError::UnknownFile(FileId::default())));
+    }
+
+    #[test]
+    fn test_line_col_encodings() {
+        use crate::prelude::*;
+        use crate::utils::line_index::{ColumnEncoding, LineCol};
+
+        // `𝄞` and `🦀` are outside of the BMP: 4 bytes, 2 UTF-16 code units, 1 char
+        let mut map = SourceMap::new();
+        let id = map.add("<lsp>", "a𝄞b\r\n\"é🦀\" 🦀x\nz");
+        let file = map.get(id).unwrap();
+        let line_col = |line, col| LineCol { line, col };
+
+        let b = BytePos::from(5);
+        assert_eq!(file.to_line_col(b, ColumnEncoding::Utf8), line_col(0, 5));
+        assert_eq!(file.to_line_col(b, ColumnEncoding::Utf16), line_col(0, 3));
+        assert_eq!(file.to_line_col(b, ColumnEncoding::Utf32), line_col(0, 2));
+
+        let x = BytePos::from(map.source(id).find('x').unwrap());
+        assert_eq!(file.to_line_col(x, ColumnEncoding::Utf16), line_col(1, 8));
+        assert_eq!(file.to_line_col(x, ColumnEncoding::Utf32), line_col(1, 6));
+        assert_eq!(file.to_byte_pos(line_col(1, 8), ColumnEncoding::Utf16), Some(x));
+        assert_eq!(file.to_byte_pos(line_col(1, 6), ColumnEncoding::Utf32), Some(x));
+
+        // Between the two halves of a surrogate pair, past the end of a line, on a missing line
+        assert_eq!(file.to_byte_pos(line_col(0, 2), ColumnEncoding::Utf16), None);
+        assert_eq!(file.to_byte_pos(line_col(0, 5), ColumnEncoding::Utf16), None);
+        assert_eq!(file.to_byte_pos(line_col(3, 0), ColumnEncoding::Utf16), None);
+        // The end of a line, before its `\r\n`
+        assert_eq!(
+            file.to_byte_pos(line_col(0, 4), ColumnEncoding::Utf16),
+            Some(BytePos::from(6))
+        );
+
+        // Every char boundary goes back and forth in every encoding
+        let source = map.source(id);
+        for pos in (0..=source.len()).filter(|&i| source.is_char_boundary(i)) {
+            if source[..pos].ends_with('\r') {
+                continue;
+            }
+            let pos = BytePos::from(pos);
+            for encoding in [ColumnEncoding::Utf8, ColumnEncoding::Utf16, ColumnEncoding::Utf32] {
+                let lc = file.to_line_col(pos, encoding);
+                assert_eq!(file.to_byte_pos(lc, encoding), Some(pos), "{:?} {:?}", pos, encoding);
+            }
+        }
+
+        let crab = map.source(id).find('🦀').unwrap();
+        let span = unsafe { Span::new_unchecked(crab, crab + '🦀'.len_utf8(), id) };
+        assert_eq!(
+            span.to_line_cols(&map, ColumnEncoding::Utf16),
+            Ok((line_col(1, 2), line_col(1, 4)))
+        );
+        assert_eq!(
+            span.to_line_cols(&map, ColumnEncoding::Utf32),
+            Ok((line_col(1, 2), line_col(1, 3)))
+        );
+        let inside = unsafe { Span::new_unchecked(crab + 1, crab + 2, id) };
+        assert_eq!(inside.to_line_cols(&map, ColumnEncoding::Utf16), Err(SpanError::NotCharBoundary(crab + 1)));
+        let unknown = unsafe { Span::new_unchecked(0, 1, FileId::default()) };
+        assert_eq!(
+            unknown.to_line_cols(&SourceMap::new(), ColumnEncoding::Utf8),
+            Err(SpanError::UnknownFile(FileId::default()))
+        );
+    }
+
+    #[test]
+    fn test_diagnostic_render() {
+        use crate::prelude::*;
+
+        let mut map = SourceMap::new();
+        let main = map.add(
+            "main.atlas",
+            "let x: int = \"a\";\nfn foo() {\n    bar(x)\n}\nlet y = foo(\n  1,\n  2,\n  3,\n  4);\n",
+        );
+        let lib = map.add("lib.atlas", "fn bar(s: str) {}\n");
+        let span = |file, text: &str, nth: usize| {
+            let source = map.source(file);
+            let start = source.match_indices(text).nth(nth).unwrap().0;
+            unsafe { Span::new_unchecked(start, start + text.len(), file) }
+        };
+        let to = |a: Span, b: Span| a.union_span(b);
+
+        let diagnostic = Diagnostic::error("mismatched types")
+            .with_code("A0042")
+            .with_label(Label::primary(span(main, "\"a\"", 0), "expected `int`, found `str`"))
+            .with_label(Label::secondary(span(main, "int", 0), "expected due to this"))
+            .with_label(Label::secondary(span(main, "x", 0), "declared here"))
+            .with_label(Label::secondary(span(lib, "s: str", 0), "parameter declared here"))
+            .with_note("`str` can't be converted to `int` implicitly")
+            .with_help("use `int(...)`");
+        assert_eq!(
+            diagnostic.render(&map, false),
+            r#"error[A0042]: mismatched types
+ --> main.atlas:1:14
+  |
+1 | let x: int = "a";
+  |     -  ---   ^^^ expected `int`, found `str`
+  |     |  |
+  |     |  expected due to this
+  |     |
+  |     declared here
+  |
+ ::: lib.atlas:1:8
+  |
+1 | fn bar(s: str) {}
+  |        ------ parameter declared here
+  |
+  = note: `str` can't be converted to `int` implicitly
+  = help: use `int(...)`
+"#
+        );
+        let colored = diagnostic.render(&map, true);
+        assert!(colored.starts_with("\x1b[1;31merror[A0042]\x1b[0m\x1b[1m: mismatched types\x1b[0m\n"));
+        assert!(colored.contains("\x1b[1;31m^^^\x1b[0m \x1b[1;31mexpected `int`, found `str`\x1b[0m\n"));
+        assert_eq!(strip_ansi(&colored), diagnostic.render(&map, false));
+
+        let diagnostic = Diagnostic::warning("function is never used")
+            .with_label(Label::primary(to(span(main, "fn", 0), span(main, "}", 0)), "this function"))
+            .with_label(Label::secondary(to(span(main, "(", 2), span(main, "4)", 0)), "this call"))
+            .with_label(Label::secondary(span(main, "bar", 0), ""));
+        assert_eq!(
+            diagnostic.render(&map, false),
+            r#"warning: function is never used
+ --> main.atlas:2:1
+  |
+2 | /  fn foo() {
+3 | |      bar(x)
+  | |      ---
+4 | |  }
+  | |__^ this function
+5 |    let y = foo(
+  |   ____________-
+6 |  |   1,
+7 |  |   2,
+8 |  |   3,
+9 |  |   4);
+  |  |____- this call
+"#
+        );
+
+        // Long spans are cut in the middle, and empty spans still get a caret
+        let mut map = SourceMap::new();
+        let file = map.add("long.atlas", "/*\n".to_owned() + &"\n".repeat(20) + "*/");
+        let len = map.source(file).len();
+        let diagnostic = Diagnostic::error("unterminated comment")
+            .with_label(Label::primary(unsafe { Span::new_unchecked(0, len, file) }, "here"))
+            .with_label(Label::secondary(unsafe { Span::new_unchecked(len, len, file) }, "end of file"));
+        assert_eq!(
+            diagnostic.render(&map, false),
+            r#"error: unterminated comment
+  --> long.atlas:1:1
+   |
+1  | / /*
+2  | |
+...  |
+21 | |
+22 | | */
+   | |   - end of file
+   | |__^ here
+"#
+        );
+        assert_eq!(
+            Diagnostic::error("no input files").with_note("pass a file").render(&map, false),
+            "error: no input files\n  = note: pass a file\n"
+        );
+    }
+
+    fn strip_ansi(text: &str) -> String {
+        let mut out = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                out.push(c);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_diagnostic_output() {
+        use crate::prelude::*;
+        use self::fixture::*;
+
+        let mut map = SourceMap::new();
+        let strings = map.add("strings.atlas", "let a = \"ok\";\nlet b = \"𝄞 never closed;\n");
+        let unknown = map.add("unknown.atlas", "let x = 1;\nx = x $ 2;");
+        let mut diagnostics = vec![];
+        for file in [strings, unknown] {
+            let mut lexer = AtlasLexer::default();
+            lexer.set_file(file).set_source(String::from(map.source(file)));
+            let diagnostic = lexer.tokenize().unwrap_err();
+            lexer.current_pos = BytePos::from(0);
+            assert_eq!(lexer.tokenize_dfa().unwrap_err(), diagnostic);
+            diagnostics.push(diagnostic);
+        }
+        let x = map.source(unknown).find('x').unwrap();
+        diagnostics.push(
+            Diagnostic::warning("variable `x` is assigned but never used")
+                .with_code("A9999")
+                .with_label(Label::primary(unsafe { Span::new_unchecked(x, x + 1, unknown) }, ""))
+                .with_label(Label::secondary(
+                    unsafe { Span::new_unchecked(11, 20, unknown) },
+                    "reassigned here",
+                ))
+                .with_note("the value is \"dropped\"\tright away")
+                .with_help("remove the variable"),
+        );
+        // Labels which can't be located are output without their lines and columns
+        let foreign = {
+            let mut other = SourceMap::new();
+            ["a.atlas", "b.atlas", "c.atlas"].map(|name| other.add(name, ""))[2]
+        };
+        let unlocated = Diagnostic::error("unused import")
+            .with_label(Label::primary(unsafe { Span::new_unchecked(0, 1, foreign) }, "imported here"))
+            .with_label(Label::secondary(unsafe { Span::new_unchecked(30, 40, unknown) }, ""));
+        let rendered = unlocated.render(&map, false);
+        assert_eq!(
+            rendered,
+            "error: unused import\n  \
+             = label: [0:1 in file #2] (imported here): the file #2 isn't in the SourceMap\n  \
+             = label: [30:40 in file #1]: the span ends at 40 but the source is only 21 bytes long\n"
+        );
+        diagnostics.push(unlocated);
+
+        let json: Vec<String> = diagnostics.iter().map(|d| d.to_json(&map)).collect();
+        assert_snapshot("diagnostics.jsonl", &(json.join("\n") + "\n"));
+        assert_snapshot("diagnostics.sarif", &Diagnostic::to_sarif(&diagnostics, &map, "atlas"));
+    }
+
+    /// Compares `actual` to the snapshot `name` in `src/tests/snapshots`.
+    ///
+    /// Run the tests with `UPDATE_SNAPSHOTS=1` to write the new output to the snapshot instead.
+    fn assert_snapshot(name: &str, actual: &str) {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src/tests/snapshots").join(name);
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(&path, actual).unwrap();
+        }
+        let expected = std::fs::read_to_string(&path).unwrap_or_default();
+        assert!(
+            expected == actual,
+            "The snapshot {} doesn't match, run with `UPDATE_SNAPSHOTS=1` to update it:\n{}",
+            name,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_error_codes() {
+        use crate::prelude::*;
+        use crate::utils::error_codes::{self, ERROR_CODES};
+
+        for (i, code) in ERROR_CODES.iter().enumerate() {
+            assert_eq!(code.code, format!("A{:04}", i + 1));
+            assert!(code.explanation.contains("Erroneous code example:"), "{}", code.code);
+            assert_eq!(error_codes::explain(code.code), Some(code.explanation));
+        }
+        assert_eq!(error_codes::lookup("a0002").map(|c| c.title), Some("unexpected character"));
+        assert_eq!(error_codes::explain("A0000"), None);
+
+        #[derive(Lexer, Debug, Clone, Copy, PartialEq)]
+        #[atlas_core(crate = "crate")]
+        enum Tok {
+            #[token("=")]
+            Eq,
+            #[ident]
+            Ident(Intern<String>),
+            #[int]
+            Int(i8),
+            #[string]
+            Str(Intern<String>),
+            #[regex(r"[ \t\r\n]+")]
+            #[skip]
+            Whitespace,
+        }
+        let code = |source: &str| Tok::tokenize(FileId::default(), source).unwrap_err().code;
+        assert_eq!(code("x = \"abc"), Some(error_codes::A0001));
+        assert_eq!(code("x = ?"), Some(error_codes::A0002));
+        assert_eq!(code("x = 300"), Some(error_codes::A0003));
+    }
+
+    #[test]
+    fn test_span_arithmetic() {
+        use crate::prelude::*;
+
+        let mut map = SourceMap::new();
+        let file = map.add("main.atlas", "print(\"hello\", 42);");
+        let other = map.add("other.atlas", "x");
+        let len = map.source(file).len();
+        let span = |start, end| Span::new(start, end, file, len).unwrap();
+
+        assert_eq!(Span::new(3, 2, file, len), Err(SpanError::Inverted { start: 3, end: 2 }));
+        assert_eq!(
+            Span::new(0, len + 1, file, len),
+            Err(SpanError::OutOfBounds { end: len + 1, len })
+        );
+        assert!(Span::new(len, len, file, len).unwrap().is_empty());
+
+        let string = span(6, 13);
+        assert_eq!(string.len(), 7);
+        assert!(!string.is_empty());
+        assert!(string.contains(BytePos::from(6)));
+        assert!(string.contains(BytePos::from(12)));
+        assert!(!string.contains(BytePos::from(13)));
+        assert!(!span(4, 4).contains(BytePos::from(4)));
+
+        assert!(string.intersects(&span(12, 16)));
+        assert!(span(0, 19).intersects(&string));
+        assert!(!string.intersects(&span(13, 16)));
+        assert!(!string.intersects(&span(0, 6)));
+        assert!(!string.intersects(&Span::new(0, 1, other, 1).unwrap()));
+
+        assert_eq!(string.shrink(1, 1), Some(span(7, 12)));
+        assert_eq!(string.shrink(7, 0), Some(span(13, 13)));
+        assert_eq!(string.shrink(4, 4), None);
+        assert_eq!(string.shrink(usize::MAX, 1), None);
+
+        assert_eq!(string.split_at(0), Some((span(6, 6), string)));
+        assert_eq!(string.split_at(1), Some((span(6, 7), span(7, 13))));
+        assert_eq!(string.split_at(7), Some((string, span(13, 13))));
+        assert_eq!(string.split_at(8), None);
+
+        assert_eq!(string.subspan(1..6), Some(span(7, 12)));
+        assert_eq!(string.subspan(7..7), Some(span(13, 13)));
+        #[allow(clippy::reversed_empty_ranges)]
+        let inverted = string.subspan(3..2);
+        assert_eq!(inverted, None);
+        assert_eq!(string.subspan(0..8), None);
+
+        let (paren, semicolon) = (span(5, 6), span(18, 19));
+        assert_eq!(paren.to(semicolon), span(5, 19));
+        assert_eq!(semicolon.to(paren), span(18, 18));
+        assert_eq!(paren.try_union(semicolon), Ok(span(5, 19)));
+        assert_eq!(semicolon.try_union(paren), Ok(span(5, 19)));
+        assert_eq!(
+            paren.try_union(Span::new(0, 1, other, 1).unwrap()),
+            Err(SpanError::DifferentFiles(file, other))
+        );
+        assert!(std::panic::catch_unwind(|| paren.union_span(Span::new(0, 1, other, 1).unwrap())).is_err());
+    }
+
+    #[test]
+    #[allow(dead_code)]
+    fn test_derive_spanned() {
+        use crate::prelude::*;
+
+        let mut map = SourceMap::new();
+        let file = map.add("main.atlas", "let add = fn(a, b) a + b;");
+        let len = map.source(file).len();
+        let span = |start, end| Span::new(start, end, file, len).unwrap();
+
+        #[derive(Spanned)]
+        #[atlas_core(crate = "crate")]
+        struct Ident(Intern<String>, #[span] Span);
+
+        #[derive(Spanned)]
+        #[atlas_core(crate = "crate")]
+        struct Function<T> {
+            params: Vec<Ident>,
+            body: Box<T>,
+            span: Span,
+        }
+
+        #[derive(Spanned)]
+        #[atlas_core(crate = "crate")]
+        enum Expr {
+            Ident(Ident),
+            Binary { lhs: Box<Expr>, op: Span, rhs: Box<Expr>, #[span] whole: Span },
+            Function(Function<Expr>),
+        }
+
+        let ident = |name: &str, start| Ident(Intern::new(name.to_owned()), span(start, start + name.len()));
+        let body = Expr::Binary {
+            lhs: Box::new(Expr::Ident(ident("a", 19))),
+            op: span(21, 22),
+            rhs: Box::new(Expr::Ident(ident("b", 23))),
+            whole: span(19, 24),
+        };
+        assert_eq!(body.span(), span(19, 24));
+        let function = Expr::Function(Function {
+            params: vec![ident("a", 13), ident("b", 16)],
+            body: Box::new(body),
+            span: span(10, 24),
+        });
+        assert_eq!(function.span(), span(10, 24));
+        let Expr::Function(f) = &function else { unreachable!() };
+        assert_eq!(f.params.span(), span(13, 17));
+        assert_eq!(f.body.span(), span(19, 24));
+
+        // Containers combine the spans of their children, skipping the ones without any
+        let none: Option<Ident> = None;
+        assert_eq!(none.try_span(), None);
+        assert_eq!(none.span(), Span::empty());
+        assert_eq!(Vec::<Ident>::new().try_span(), None);
+        assert_eq!((ident("let", 0), none, vec![ident("add", 4)]).span(), span(0, 7));
+        assert_eq!((Some(span(12, 13)), Vec::<Span>::new()).span(), span(12, 13));
+        assert_eq!([span(4, 7), span(0, 3)].as_slice().span(), span(0, 7));
+    }
+
+    #[test]
+    fn test_node() {
+        use crate::prelude::*;
+
+        let mut map = SourceMap::new();
+        let file = map.add("main.atlas", "let name = \"atlas\";");
+        let len = map.source(file).len();
+        let span = |start, end| Span::new(start, end, file, len).unwrap();
+
+        let mut name = Node::new(String::from("atlas"), span(11, 18));
+        // `Deref` gives access to the methods of the value
+        assert_eq!(name.len(), 5);
+        name.push('!');
+        assert_eq!(name.as_str(), "atlas!");
+        assert_eq!(name.to_string(), "atlas!");
+
+        let borrowed: Node<&String> = name.as_ref();
+        assert_eq!(borrowed.span, name.span);
+        let length = name.as_ref().map(|s| s.len());
+        assert_eq!(length, Node::new(6, span(11, 18)));
+        name.as_mut().value.clear();
+        assert!(name.is_empty());
+
+        #[derive(Spanned)]
+        #[atlas_core(crate = "crate")]
+        enum Pattern {
+            Name(Node<Intern<String>>),
+            Tuple(Node<Vec<Pattern>>),
+        }
+        let pattern = Pattern::Tuple(Node::new(
+            vec![Pattern::Name(Node::new(Intern::new(String::from("name")), span(4, 8)))],
+            span(3, 9),
+        ));
+        assert_eq!(pattern.span(), span(3, 9));
+        let Pattern::Tuple(items) = pattern else { unreachable!() };
+        assert_eq!(items.into_inner().span(), span(4, 8));
+    }
+
+    #[test]
+    fn test_snippet() {
+        use crate::prelude::*;
+        use self::fixture::*;
+
+        let mut map = SourceMap::new();
+        let file = map.add("main.atlas", "let café = \"thé\";");
+        let mut lexer = AtlasLexer::default();
+        lexer.set_file(file).set_source(String::from(map.source(file)));
+        let tokens = lexer.tokenize().unwrap();
+        let texts: Vec<&str> = tokens.iter().map(|t| t.text(map.source(file)).unwrap()).collect();
+        assert_eq!(texts, ["", "let", "café", "=", "\"thé\"", ";", ""]);
+        for token in &tokens {
+            assert_eq!(map.snippet(token.span()), token.text(&lexer.source));
+        }
+
+        // `é` takes the bytes 7 and 8
+        let span = |start, end| unsafe { Span::new_unchecked(start, end, file) };
+        assert_eq!(map.snippet(span(4, 9)), Ok("café"));
+        assert_eq!(map.snippet(span(4, 8)), Err(SpanError::NotCharBoundary(8)));
+        assert_eq!(map.snippet(span(8, 9)), Err(SpanError::NotCharBoundary(8)));
+        assert_eq!(map.snippet(span(9, 4)), Err(SpanError::Inverted { start: 9, end: 4 }));
+        assert_eq!(map.snippet(span(4, 40)), Err(SpanError::OutOfBounds { end: 40, len: 19 }));
+        let unknown = unsafe { Span::new_unchecked(0, 1, FileId::default()) };
+        assert_eq!(SourceMap::new().snippet(unknown), Err(SpanError::UnknownFile(FileId::default())));
+
+        #[derive(Lexer, Debug, Clone, Copy, PartialEq)]
+        #[atlas_core(crate = "crate")]
+        enum Tok {
+            #[ident]
+            Ident(Intern<String>),
+            #[regex(r"[ \t\r\n]+")]
+            #[skip]
+            Whitespace,
+        }
+        let source = "voilà ça";
+        let lexemes = Tok::tokenize(file, source).unwrap();
+        assert_eq!(lexemes[1].text(source), Ok("ça"));
+        assert!(lexemes[1].text("voilà").is_err());
+    }
+
+    #[test]
+    fn test_macro_expansion() {
+        use crate::prelude::*;
+
+        let mut map = SourceMap::new();
+        let file = map.add(
+            "main.atlas",
+            "macro square(x) { x * x }\nmacro area(r) { 3 * square!(r) }\nlet a = area!(\"one\");\n",
+        );
+        let source = map.source(file).to_owned();
+        let span = |text: &str| {
+            let start = source.find(text).unwrap();
+            Span::new(start, start + text.len(), file, source.len()).unwrap()
+        };
+
+        let area = map.add_expansion("area", span("area!(\"one\")"), span("macro area(r) { 3 * square!(r) }"));
+        let square = map.add_expansion(
+            "square",
+            span("square!(r)").with_expansion(area),
+            span("macro square(x) { x * x }"),
+        );
+        assert!(!span("let").is_from_expansion());
+        assert_eq!(map.expansions().len(), 2);
+
+        // The `x * x` produced by `square!`, itself invoked by `area!`
+        let product = span("x * x").with_expansion(square);
+        assert_eq!(product.call_site(map.expansions()), Some(span("square!(r)").with_expansion(area)));
+        assert_eq!(product.def_site(map.expansions()), Some(span("macro square(x) { x * x }")));
+        let names: Vec<&str> = product
+            .macro_backtrace(map.expansions())
+            .map(|e| e.macro_name.as_str())
+            .collect();
+        assert_eq!(names, ["square", "area"]);
+        assert_eq!(product.source_callsite(map.expansions()), span("area!(\"one\")"));
+        assert_eq!(span("let").source_callsite(map.expansions()), span("let"));
+        assert_eq!(span("let").macro_backtrace(map.expansions()).count(), 0);
+
+        let diagnostic = Diagnostic::error("cannot multiply `str` by `str`")
+            .with_label(Label::primary(product, "no implementation for `str * str`"));
+        assert_eq!(
+            diagnostic.render(&map, false),
+            r#"error: cannot multiply `str` by `str`
+ --> main.atlas:1:19
+  |
+1 | macro square(x) { x * x }
+  |                   ^^^^^ no implementation for `str * str`
+2 | macro area(r) { 3 * square!(r) }
+  |                     ---------- in this macro invocation
+3 | let a = area!("one");
+  |         ------------ in this macro invocation
+  |
+  = note: this error originates in the macro `square`
+  = note: this error originates in the macro `area`
+"#
+        );
+        // Diagnostics without expansions are left as they are
+        let plain = Diagnostic::error("plain").with_label(Label::primary(span("let"), ""));
+        assert!(!plain.render(&map, false).contains("macro"));
+    }
+
+    #[test]
+    fn test_case() {
+        use crate::prelude::*;
+        use std::borrow::Cow;
+
+        let detected: Vec<CaseStyle> = [
+            "max_size", "MAX_SIZE", "maxSize", "MaxSize", "max-size", "Max-Size", "Max Size", "max_Size",
+        ]
+        .iter()
+        .map(|ident| ident.detect_case())
+        .collect();
+        assert_eq!(detected[..7], CaseStyle::ALL);
+        assert_eq!(detected[7], CaseStyle::Mixed);
+        // A single word follows several conventions, the first one wins
+        assert_eq!("name".detect_case(), CaseStyle::Snake);
+        assert_eq!("NAME".detect_case(), CaseStyle::ShoutySnake);
+        assert_eq!("Name".detect_case(), CaseStyle::Pascal);
+
+        let ident = "httpServer";
+        assert_eq!(ident.to_snake(), "http_server");
+        assert_eq!(ident.to_shouty_snake(), "HTTP_SERVER");
+        assert_eq!(ident.to_camel(), "httpServer");
+        assert_eq!(ident.to_pascal(), "HttpServer");
+        assert_eq!(ident.to_kebab(), "http-server");
+        assert_eq!(ident.to_train(), "Http-Server");
+        assert_eq!(ident.to_title(), "Http Server");
+        assert_eq!(ident.to_case(CaseStyle::Mixed), "httpServer");
+
+        // Leading underscores are kept and ignored
+        assert!("_unused".is_snake_case());
+        assert!("__Private".is_pascal_case());
+        assert_eq!("_unused_value".to_pascal(), "_UnusedValue");
+        assert_eq!("__Init".to_snake(), "__init");
+        assert_eq!("_".detect_case(), CaseStyle::Snake);
+        assert!("__".is_pascal_case());
+        // Digits belong to the word before them
+        assert!("vec3".is_snake_case());
+        assert!("Vec3".is_pascal_case());
+        assert!("VEC3".is_shouty_snake_case());
+        assert_eq!("point_2d".to_pascal(), "Point2d");
+
+        // Every string type has the same behaviour
+        let owned = String::from("MaxSize");
+        let cow: Cow<str> = Cow::Borrowed("MaxSize");
+        let interned = Intern::new(String::from("MaxSize"));
+        let idents: [&dyn Case; 4] = [&"MaxSize", &owned, &cow, &interned];
+        for ident in idents {
+            assert!(ident.is_pascal_case());
+            assert_eq!(ident.detect_case(), CaseStyle::Pascal);
+            assert_eq!(ident.to_snake(), "max_size");
+        }
+    }
+
+    #[test]
+    fn test_naming_lints() {
+        use crate::prelude::*;
+        use crate::utils::{case::CaseStyle, error_codes::A0004, naming::NamingLints};
+        use self::fixture::*;
+
+        let mut map = SourceMap::new();
+        let file = map.add(
+            "main.atlas",
+            "struct point_xy {}\nconst maxSize = 10;\nfn DoThings() {}\nlet good_name = maxSize;\n",
+        );
+        let mut lexer = AtlasLexer::default();
+        lexer.set_file(file).set_source(String::from(map.source(file)));
+        let tokens = lexer.tokenize().unwrap();
+
+        let diagnostics = NamingLints::default().check(&tokens);
+        let summary: Vec<(&str, &str, Option<&str>)> = diagnostics
+            .iter()
+            .map(|d| (map.snippet(d.primary_span().unwrap()).unwrap(), d.message.as_str(), d.help.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("point_xy", "type `point_xy` should have a PascalCase name", Some("rename it to `PointXy`")),
+                ("maxSize", "constant `maxSize` should have a SHOUTY_SNAKE_CASE name", Some("rename it to `MAX_SIZE`")),
+                ("DoThings", "function `DoThings` should have a snake_case name", Some("rename it to `do_things`")),
+            ]
+        );
+        assert!(diagnostics.iter().all(|d| d.severity == Severity::Warning && d.code == Some(A0004)));
+        assert_eq!(
+            diagnostics[2].render(&map, false),
+            "warning[A0004]: function `DoThings` should have a snake_case name
+ --> main.atlas:3:4
+  |
+3 | fn DoThings() {}
+  |    ^^^^^^^^ not in snake_case
+  |
+  = help: rename it to `do_things`
+"
+        );
+
+        // Only the configured keywords are checked, and a rule can be overridden
+        let lints = NamingLints::new()
+            .with_rule("fn", "function", CaseStyle::Snake)
+            .with_rule("fn", "function", CaseStyle::Pascal);
+        assert_eq!(lints.rules().len(), 1);
+        assert!(lints.check(&tokens).is_empty());
+    }
+
+    #[test]
+    #[allow(dead_code)]
+    fn test_token_stream() {
+        use crate::prelude::*;
+
+        lexer_builder! {
+            DefaultSystem {
+                number: true,
+                symbol: true,
+                keyword: true,
+                string: true,
+                whitespace: {
+                    allow_them: true,
+                    use_system: true,
+                },
+            },
+            Symbols {
+                Single {
+                    '(' => LParen,
+                    ')' => RParen,
+                    ';' => Semicolon,
+                },
+                Either {
+                    '=' => '=' => OpEq, OpAssign,
+                }
+            },
+            Keyword {
+                "let",
+            },
+            Number {
+                trailing {
+                    "_i8" => i8 => I8,
+                },
+                float: true,
+                u_int: true,
+                int: true
+            },
+        }
+
+        let mut map = SourceMap::new();
+        let file = map.add("main.atlas", "let x =\n\t(1);\n");
+        let mut lexer = AtlasLexer::default();
+        lexer.set_file(file).set_source(String::from(map.source(file)));
+        let tokens = lexer.tokenize().unwrap();
+        assert!(tokens.iter().any(|t| t.kind() == TokenKind::NewLine));
+
+        let text = |token: Option<Token>| map.snippet(token.unwrap().span()).unwrap();
+        let mut stream = TokenStream::new(tokens.clone());
+        assert_eq!(text(stream.peek()), "let");
+        assert_eq!(text(stream.peek_nth(2)), "=");
+        assert_eq!(text(stream.peek_nth(3)), "(");
+        assert!(stream.peek_nth(7).is_none());
+
+        let checkpoint = stream.checkpoint();
+        assert!(stream.eat(TokenTag::LParen).is_none());
+        // The tags ignore the payload, the `_kind` methods compare it too
+        assert!(stream.at(TokenTag::Keyword));
+        assert!(stream.eat_kind(TokenKind::Keyword(Intern::new(String::from("if")))).is_none());
+        assert_eq!(text(stream.eat_kind(TokenKind::Keyword(Intern::new(String::from("let"))))), "let");
+        assert!(stream.at_kind(TokenKind::Literal(Literal::Identifier(Intern::new(String::from("x"))))));
+        assert_eq!(text(stream.expect(TokenTag::Identifier).ok()), "x");
+        assert_eq!(text(stream.expect(TokenTag::OpAssign).ok()), "=");
+        let error = stream.expect(TokenTag::Semicolon).unwrap_err();
+        assert_eq!(error.message, "expected `;`, found `(`");
+        assert_eq!(error.code, Some(crate::utils::error_codes::A0005));
+        assert_eq!(map.snippet(error.primary_span().unwrap()), Ok("("));
+        assert!(stream.at(TokenTag::LParen));
+        let error = stream.expect(TokenTag::Identifier).unwrap_err();
+        assert_eq!(error.message, "expected an identifier, found `(`");
+        let error = stream.expect_kind(TokenKind::Keyword(Intern::new(String::from("let")))).unwrap_err();
+        assert_eq!(error.message, "expected keyword `let`, found `(`");
+        assert_eq!(text(stream.expect_kind(TokenKind::LParen).ok()), "(");
+        let error = stream.expect_kind(TokenKind::Literal(Literal::Int(2))).unwrap_err();
+        assert_eq!(error.message, "expected `2`, found `1`");
+        let error = stream.expect(TokenTag::OpEq).unwrap_err();
+        assert_eq!(error.message, "expected `==`, found `1`");
+        stream.rewind(checkpoint);
+        stream.expect(TokenTag::Keyword).unwrap();
+        stream.expect(TokenTag::Identifier).unwrap();
+        stream.expect(TokenTag::OpAssign).unwrap();
+        assert_eq!(TokenKind::Literal(Literal::Identifier(Intern::new(String::from("x")))).to_string(), "identifier `x`");
+        assert_eq!(TokenKind::Literal(Literal::StringLiteral(Intern::new(String::from("a\"b")))).to_string(), r#"string "a\"b""#);
+        assert_eq!(TokenKind::Literal(Literal::I8(-3)).to_string(), "`-3`");
+
+        let rest: Vec<&str> = stream.by_ref().map(|t| text(Some(t))).collect();
+        assert_eq!(rest, ["(", "1", ")", ";"]);
+        assert!(stream.is_at_end());
+        let end = stream.checkpoint();
+        assert!(stream.bump().is_none());
+        let error = stream.expect(TokenTag::RParen).unwrap_err();
+        assert_eq!(error.message, "expected `)`, found the end of the input");
+        assert_eq!(
+            error.render(&map, false),
+            "error[A0005]: expected `)`, found the end of the input
+ --> main.atlas:2:6
+  |
+2 |  (1);
+  |      ^ expected `)` here
+"
+        );
+
+        stream.rewind(checkpoint);
+        assert_eq!(text(stream.peek()), "let");
+        assert_eq!(stream.count(), 7);
+        assert!(TokenStream::from(vec![]).peek().is_none());
+        // A checkpoint of a longer stream only moves a shorter one to its end
+        let mut short = TokenStream::new(tokens[..3].to_vec());
+        assert_eq!(text(short.peek()), "let");
+        short.rewind(end);
+        assert!(short.is_at_end());
+        assert!(short.peek_nth(1).is_none());
+        short.rewind(checkpoint);
+        assert_eq!(text(short.peek()), "let");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        use crate::prelude::*;
+        use self::fixture::*;
+
+        let mut map = SourceMap::new();
+        let file = map.add("main.atlas", "let color = #ff8800; let s = \"text\"; let f = 2.5 == 4;");
+        let mut lexer = AtlasLexer::default();
+        lexer.set_file(file).set_source(String::from(map.source(file)));
+        let tokens = lexer.tokenize().unwrap();
+        let json = serde_json::to_string(&tokens).unwrap();
+        let back: Vec<Token> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, tokens);
+        // The interned strings are interned again when deserialised
+        let TokenKind::Literal(Literal::StringLiteral(s)) = back[9].kind() else {
+            panic!("{:?}", back[9])
+        };
+        assert_eq!(s, Intern::new(String::from("text")));
+
+        let span = tokens[1].span().with_expansion(map.add_expansion("m", tokens[2].span(), tokens[3].span()));
+        let json = serde_json::to_string(&span).unwrap();
+        assert_eq!(serde_json::from_str::<Span>(&json).unwrap(), span);
+        let info = span.get_line_info(&map).unwrap();
+        let json = serde_json::to_string(&info).unwrap();
+        assert_eq!(serde_json::from_str::<LineInformation>(&json).unwrap(), info);
+
+        #[derive(Lexer, Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+        #[atlas_core(crate = "crate")]
+        enum Tok {
+            #[ident]
+            Ident(Intern<String>),
+            #[regex(r"[ \t\r\n]+")]
+            #[skip]
+            Whitespace,
+        }
+        let lexemes = Tok::tokenize(file, "hello world").unwrap();
+        let json = serde_json::to_string(&lexemes).unwrap();
+        assert_eq!(serde_json::from_str::<Vec<Lexeme<Tok>>>(&json).unwrap(), lexemes);
+    }
 }
@@ -1,75 +1,243 @@
 use heck::*;
+use internment::Intern;
+use std::borrow::Cow;
 
-/// The `Case` trait defines methods for checking the naming convention of identifiers.
+/// The `Case` trait defines methods for checking, detecting and converting the naming convention of identifiers.
 ///
-/// NB: It's already implemented in ``String`` and ``&str`` by default, so you don't need to implement it yourself.
+/// Leading underscores are kept as they are and ignored by the checks and the conversions,
+/// so `_unused` is in snake_case and `_unused.to_pascal()` is `_Unused`. An identifier made only of
+/// underscores follows every convention.
+///
+/// Digits belong to the word they follow and have no case of their own: `vec3` is in snake_case,
+/// `Vec3` in PascalCase and `VEC3` in SHOUTY_SNAKE_CASE. When converting, a digit never starts a new word
+/// (`point_2d.to_pascal()` is `Point2d`).
+///
+/// NB: It's already implemented in ``str``, ``String``, ``Cow<str>`` and ``Intern<String>`` (and references to them)
+/// by default, so you don't need to implement it yourself.
+///
+/// # Example
+///
+/// ```
+/// use atlas_core::utils::case::{Case, CaseStyle};
+///
+/// assert_eq!("max_size".detect_case(), CaseStyle::Snake);
+/// assert_eq!("maxSize".to_shouty_snake(), "MAX_SIZE");
+/// assert!("_Unused".is_pascal_case());
+/// assert_eq!("max_Size".detect_case(), CaseStyle::Mixed);
+/// ```
 pub trait Case {
+    /// Returns the identifier as a `&str`
+    fn as_ident(&self) -> &str;
+
     /// Checks if an identifier follows the snake_case naming convention.
     ///
     /// # Returns
     ///
     /// `true` if the identifier is in snake_case, `false` otherwise.
-    fn is_snake_case(&mut self) -> bool;
+    fn is_snake_case(&self) -> bool {
+        CaseStyle::Snake.matches(self.as_ident())
+    }
     /// Checks if an identifier follows the SHOUTY_SNAKE_CASE naming convention.
     ///
     /// # Returns
     ///
     /// `true` if the identifier is in SHOUTY_SNAKE_CASE, `false` otherwise.
-    fn is_shouty_snake_case(&mut self) -> bool;
+    fn is_shouty_snake_case(&self) -> bool {
+        CaseStyle::ShoutySnake.matches(self.as_ident())
+    }
     /// Checks if an identifier follows the PascalCase naming convention.
     ///
     /// # Returns
     ///
     /// `true` if the identifier is in PascalCase, `false` otherwise.
-    fn is_pascal_case(&mut self) -> bool;
+    fn is_pascal_case(&self) -> bool {
+        CaseStyle::Pascal.matches(self.as_ident())
+    }
     /// Checks if an identifier follows the camelCase naming convention.
     ///
     /// # Returns
     ///
     /// `true` if the identifier is in camelCase, `false` otherwise.
-    fn is_camel_case(&mut self) -> bool;
+    fn is_camel_case(&self) -> bool {
+        CaseStyle::Camel.matches(self.as_ident())
+    }
+    /// Checks if an identifier follows the kebab-case naming convention.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the identifier is in kebab-case, `false` otherwise.
+    fn is_kebab_case(&self) -> bool {
+        CaseStyle::Kebab.matches(self.as_ident())
+    }
+    /// Checks if an identifier follows the Train-Case naming convention.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the identifier is in Train-Case, `false` otherwise.
+    fn is_train_case(&self) -> bool {
+        CaseStyle::Train.matches(self.as_ident())
+    }
+    /// Checks if an identifier follows the Title Case naming convention.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the identifier is in Title Case, `false` otherwise.
+    fn is_title_case(&self) -> bool {
+        CaseStyle::Title.matches(self.as_ident())
+    }
+
+    /// Returns the naming convention the identifier follows.
+    ///
+    /// A single word follows several conventions at once, the first one in the order of the
+    /// variants of [`CaseStyle`] is returned: `name` is [`CaseStyle::Snake`], `NAME` is
+    /// [`CaseStyle::ShoutySnake`] and `Name` is [`CaseStyle::Pascal`].
+    /// An identifier following none of them is [`CaseStyle::Mixed`].
+    fn detect_case(&self) -> CaseStyle {
+        let ident = self.as_ident();
+        CaseStyle::ALL
+            .into_iter()
+            .find(|style| style.matches(ident))
+            .unwrap_or(CaseStyle::Mixed)
+    }
+
+    /// Converts the identifier to `style`
+    fn to_case(&self, style: CaseStyle) -> String {
+        style.convert(self.as_ident())
+    }
+    /// Converts the identifier to snake_case
+    fn to_snake(&self) -> String {
+        self.to_case(CaseStyle::Snake)
+    }
+    /// Converts the identifier to SHOUTY_SNAKE_CASE
+    fn to_shouty_snake(&self) -> String {
+        self.to_case(CaseStyle::ShoutySnake)
+    }
+    /// Converts the identifier to PascalCase
+    fn to_pascal(&self) -> String {
+        self.to_case(CaseStyle::Pascal)
+    }
+    /// Converts the identifier to camelCase
+    fn to_camel(&self) -> String {
+        self.to_case(CaseStyle::Camel)
+    }
+    /// Converts the identifier to kebab-case
+    fn to_kebab(&self) -> String {
+        self.to_case(CaseStyle::Kebab)
+    }
+    /// Converts the identifier to Train-Case
+    fn to_train(&self) -> String {
+        self.to_case(CaseStyle::Train)
+    }
+    /// Converts the identifier to Title Case
+    fn to_title(&self) -> String {
+        self.to_case(CaseStyle::Title)
+    }
 }
 
-impl Case for String {
+impl Case for str {
     #[inline(always)]
-    fn is_snake_case(&mut self) -> bool {
-        self.to_snake_case() == *self
+    fn as_ident(&self) -> &str {
+        self
     }
+}
 
+impl Case for String {
     #[inline(always)]
-    fn is_camel_case(&mut self) -> bool {
-        self.to_lower_camel_case() == *self
+    fn as_ident(&self) -> &str {
+        self
     }
+}
 
+impl Case for Cow<'_, str> {
     #[inline(always)]
-    fn is_pascal_case(&mut self) -> bool {
-        self.to_pascal_case() == *self
+    fn as_ident(&self) -> &str {
+        self
     }
+}
 
+impl Case for Intern<String> {
     #[inline(always)]
-    fn is_shouty_snake_case(&mut self) -> bool {
-        self.to_shouty_snake_case() == *self
+    fn as_ident(&self) -> &str {
+        self.as_str()
     }
 }
 
-impl Case for &str {
+impl<T: Case + ?Sized> Case for &T {
     #[inline(always)]
-    fn is_snake_case(&mut self) -> bool {
-        self.to_snake_case() == *self
+    fn as_ident(&self) -> &str {
+        (**self).as_ident()
     }
+}
 
-    #[inline(always)]
-    fn is_camel_case(&mut self) -> bool {
-        self.to_lower_camel_case() == *self
+/// A naming convention an identifier can be checked against and converted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CaseStyle {
+    /// `snake_case`
+    Snake,
+    /// `SHOUTY_SNAKE_CASE`
+    ShoutySnake,
+    /// `camelCase`
+    Camel,
+    /// `PascalCase`
+    Pascal,
+    /// `kebab-case`
+    Kebab,
+    /// `Train-Case`
+    Train,
+    /// `Title Case`
+    Title,
+    /// None of the other conventions (`max_Size`).
+    ///
+    /// Used as a convention, it accepts any identifier and leaves it as it is.
+    Mixed,
+}
+
+impl CaseStyle {
+    /// Every convention but [`CaseStyle::Mixed`], in the order [`Case::detect_case`] tries them
+    pub const ALL: [CaseStyle; 7] = [
+        CaseStyle::Snake,
+        CaseStyle::ShoutySnake,
+        CaseStyle::Camel,
+        CaseStyle::Pascal,
+        CaseStyle::Kebab,
+        CaseStyle::Train,
+        CaseStyle::Title,
+    ];
+
+    /// Returns `true` if `ident` follows this convention.
+    pub fn matches(self, ident: &str) -> bool {
+        self.convert(ident) == ident
     }
 
-    #[inline(always)]
-    fn is_pascal_case(&mut self) -> bool {
-        self.to_pascal_case() == *self
+    /// Converts `ident` to this convention, using `heck`. The leading underscores are kept.
+    pub fn convert(self, ident: &str) -> String {
+        let words = ident.trim_start_matches('_');
+        let mut converted = String::from(&ident[..ident.len() - words.len()]);
+        converted.push_str(&match self {
+            CaseStyle::Snake => words.to_snake_case(),
+            CaseStyle::ShoutySnake => words.to_shouty_snake_case(),
+            CaseStyle::Camel => words.to_lower_camel_case(),
+            CaseStyle::Pascal => words.to_pascal_case(),
+            CaseStyle::Kebab => words.to_kebab_case(),
+            CaseStyle::Train => words.to_train_case(),
+            CaseStyle::Title => words.to_title_case(),
+            CaseStyle::Mixed => words.to_owned(),
+        });
+        converted
     }
+}
 
-    #[inline(always)]
-    fn is_shouty_snake_case(&mut self) -> bool {
-        self.to_shouty_snake_case() == *self
+impl core::fmt::Display for CaseStyle {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            CaseStyle::Snake => "snake_case",
+            CaseStyle::ShoutySnake => "SHOUTY_SNAKE_CASE",
+            CaseStyle::Camel => "camelCase",
+            CaseStyle::Pascal => "PascalCase",
+            CaseStyle::Kebab => "kebab-case",
+            CaseStyle::Train => "Train-Case",
+            CaseStyle::Title => "Title Case",
+            CaseStyle::Mixed => "mixed case",
+        })
     }
 }
@@ -0,0 +1,827 @@
+use crate::utils::{
+    error_codes,
+    json::Json,
+    line_index::ColumnEncoding,
+    source_map::{FileId, SourceFile, SourceMap},
+    span::{BytePos, LineInformation, Span, SpanError},
+};
+use core::fmt;
+use std::collections::BTreeSet;
+
+const BOLD: &str = "1";
+const BLUE: &str = "1;34";
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub enum Severity {
+    /// Something that prevents the source from being used
+    Error,
+    /// Something suspicious, that doesn't prevent the source from being used
+    Warning,
+    /// An information
+    Note,
+    /// A suggestion
+    Help,
+}
+
+impl Severity {
+    fn color(self) -> &'static str {
+        match self {
+            Severity::Error => "1;31",
+            Severity::Warning => "1;33",
+            Severity::Note => "1;32",
+            Severity::Help => "1;36",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+            Severity::Help => "help",
+        })
+    }
+}
+
+/// Whether a [`Label`] points at the cause of a [`Diagnostic`] or gives some context.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum LabelStyle {
+    /// The span causing the diagnostic, underlined with `^`
+    Primary,
+    /// A span related to the diagnostic, underlined with `-`
+    Secondary,
+}
+
+/// A message attached to a `Span` of a [`Diagnostic`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    /// Whether the label is primary or secondary
+    pub style: LabelStyle,
+    /// The span the label points at
+    pub span: Span,
+    /// The message shown next to the span, it can be empty
+    pub message: String,
+}
+
+impl Label {
+    /// Creates a primary label
+    pub fn primary(span: Span, message: impl Into<String>) -> Self {
+        Label {
+            style: LabelStyle::Primary,
+            span,
+            message: message.into(),
+        }
+    }
+
+    /// Creates a secondary label
+    pub fn secondary(span: Span, message: impl Into<String>) -> Self {
+        Label {
+            style: LabelStyle::Secondary,
+            span,
+            message: message.into(),
+        }
+    }
+}
+
+/// `Diagnostic` is an error, a warning, ... about a source, with the spans it's about.
+///
+/// It's rendered like the diagnostics of rustc, with the lines of the labels, their line numbers
+/// and underlines below the labelled spans.
+///
+/// # Example
+///
+/// ```
+/// use atlas_core::prelude::*;
+///
+/// let mut map = SourceMap::new();
+/// let file = map.add("main.atlas", "let s = \"abc;\n");
+/// let span = Span::new(8, 13, file, map.source(file).len()).unwrap();
+/// let diagnostic = Diagnostic::error("unterminated string")
+///     .with_code("A0001")
+///     .with_label(Label::primary(span, "this string is never closed"))
+///     .with_help("add a `\"` at the end of the string");
+///
+/// assert_eq!(
+///     diagnostic.render(&map, false),
+///     "\
+/// error[A0001]: unterminated string
+///  --> main.atlas:1:9
+///   |
+/// 1 | let s = \"abc;
+///   |         ^^^^^ this string is never closed
+///   |
+///   = help: add a `\"` at the end of the string
+/// "
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// How serious the diagnostic is
+    pub severity: Severity,
+    /// The code identifying the kind of diagnostic (e.g. `A0001`)
+    pub code: Option<&'static str>,
+    /// The main message
+    pub message: String,
+    /// The spans the diagnostic is about
+    pub labels: Vec<Label>,
+    /// Additional information shown after the snippets
+    pub notes: Vec<String>,
+    /// How to fix the problem
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    /// Creates a new `Diagnostic` without any label
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity,
+            code: None,
+            message: message.into(),
+            labels: vec![],
+            notes: vec![],
+            help: None,
+        }
+    }
+
+    /// Creates a new error
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    /// Creates a new warning
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    /// Sets the code of the diagnostic
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Adds a label
+    pub fn with_label(mut self, label: Label) -> Self {
+        self.labels.push(label);
+        self
+    }
+
+    /// Adds a note
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Sets the help text
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Returns the span of the first primary label, or of the first label if none is primary
+    pub fn primary_span(&self) -> Option<Span> {
+        self.labels
+            .iter()
+            .find(|l| l.style == LabelStyle::Primary)
+            .or_else(|| self.labels.first())
+            .map(|l| l.span)
+    }
+
+    /// Renders the diagnostic, with the snippets of the files of its labels taken from `map`.
+    ///
+    /// If `colored` is `true`, the output contains ANSI escape codes for the terminal.
+    ///
+    /// When a label comes from a macro expansion (see [`ExpansionTable`](crate::utils::span::ExpansionTable)),
+    /// the invocations of the macros are shown with an "in this macro invocation" label, and a note
+    /// names the macro the diagnostic originates in.
+    ///
+    /// A label which can't be located in `map` (its file isn't in it, or it's out of the bounds of
+    /// the file) has no snippet: it's listed after the snippets with the reason instead.
+    pub fn render(&self, map: &SourceMap, colored: bool) -> String {
+        match self.with_macro_backtrace(map) {
+            Some(expanded) => Renderer::new(&expanded, map).render(colored),
+            None => Renderer::new(self, map).render(colored),
+        }
+    }
+
+    /// Adds the labels and notes showing the macro invocations the labels come from,
+    /// `None` if no label comes from a macro expansion.
+    fn with_macro_backtrace(&self, map: &SourceMap) -> Option<Diagnostic> {
+        if !self.labels.iter().any(|l| l.span.is_from_expansion()) {
+            return None;
+        }
+        let mut expanded = self.clone();
+        for label in &self.labels {
+            for expansion in label.span.macro_backtrace(map.expansions()) {
+                if !expanded.labels.iter().any(|l| l.span == expansion.call_site) {
+                    expanded
+                        .labels
+                        .push(Label::secondary(expansion.call_site, "in this macro invocation"));
+                }
+                let note = format!(
+                    "this {} originates in the macro `{}`",
+                    self.severity, expansion.macro_name
+                );
+                if !expanded.notes.contains(&note) {
+                    expanded.notes.push(note);
+                }
+            }
+        }
+        Some(expanded)
+    }
+
+    /// Serialises the diagnostic to a single line of JSON, with the files of its labels taken from `map`.
+    ///
+    /// The schema is stable, keys always come in this order and absent values are `null`:
+    ///
+    /// ```text
+    /// {
+    ///   "severity": "error" | "warning" | "note" | "help",
+    ///   "code": string | null,
+    ///   "message": string,
+    ///   "labels": [{
+    ///     "style": "primary" | "secondary",
+    ///     "message": string,
+    ///     "file": string | null,
+    ///     "byte_start": number, "byte_end": number,
+    ///     "line_start": number | null, "column_start": number | null,
+    ///     "line_end": number | null, "column_end": number | null
+    ///   }],
+    ///   "notes": [string],
+    ///   "help": string | null
+    /// }
+    /// ```
+    ///
+    /// Lines and columns are 1-based, columns are counted in chars, and the ends are exclusive.
+    /// They're `null` for a label which can't be located in `map`, and so is the file if it isn't in `map`.
+    pub fn to_json(&self, map: &SourceMap) -> String {
+        let labels = self.labels.iter().map(|label| {
+            let info = label.span.get_line_info(map).ok();
+            let line_col = |f: fn(&LineInformation) -> usize| info.as_ref().map(f).into();
+            Json::object([
+                (
+                    "style",
+                    match label.style {
+                        LabelStyle::Primary => "primary",
+                        LabelStyle::Secondary => "secondary",
+                    }
+                    .into(),
+                ),
+                ("message", label.message.as_str().into()),
+                ("file", map.get(label.span.file).map(SourceFile::name).into()),
+                ("byte_start", usize::from(label.span.start).into()),
+                ("byte_end", usize::from(label.span.end).into()),
+                ("line_start", line_col(|i| i.start_line)),
+                ("column_start", line_col(|i| i.start_column)),
+                ("line_end", line_col(|i| i.end_line)),
+                ("column_end", line_col(|i| i.end_column)),
+            ])
+        });
+        Json::object([
+            ("severity", self.severity.to_string().as_str().into()),
+            ("code", self.code.into()),
+            ("message", self.message.as_str().into()),
+            ("labels", Json::Array(labels.collect())),
+            (
+                "notes",
+                Json::Array(self.notes.iter().map(|n| n.as_str().into()).collect()),
+            ),
+            ("help", self.help.as_deref().into()),
+        ])
+        .to_compact()
+    }
+
+    /// Serialises `diagnostics` to a [SARIF 2.1.0](https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html)
+    /// log, the format read by code-scanning dashboards.
+    ///
+    /// Every diagnostic is a `result` of a single run of the tool named `tool`, and every code is
+    /// a `rule` of the tool, described with its explanation when it's registered in
+    /// [`error_codes`](crate::utils::error_codes). The primary labels are the `locations` of the result, the secondary
+    /// ones its `relatedLocations`. Columns are counted in UTF-16 code units, as the spec requires
+    /// by default. A label which can't be located in `map` only gets its byte offsets, and the
+    /// description of its file instead of its `uri` if the file isn't in `map`.
+    pub fn to_sarif(diagnostics: &[Diagnostic], map: &SourceMap, tool: &str) -> String {
+        let mut codes: Vec<&'static str> = diagnostics.iter().filter_map(|d| d.code).collect();
+        codes.sort_unstable();
+        codes.dedup();
+        // The registered codes come with their descriptions
+        let rules = codes.iter().map(|&code| match error_codes::lookup(code) {
+            Some(registered) => Json::object([
+                ("id", code.into()),
+                ("shortDescription", Json::object([("text", registered.title.into())])),
+                (
+                    "help",
+                    Json::object([
+                        ("text", registered.explanation.into()),
+                        ("markdown", registered.explanation.into()),
+                    ]),
+                ),
+            ]),
+            None => Json::object([("id", code.into())]),
+        });
+
+        let results = diagnostics.iter().map(|d| {
+            let location = |label: &Label| {
+                let mut region = vec![];
+                // A label which can't be located in `map` only keeps its byte offsets
+                if let Ok((start, end)) = label.span.to_line_cols(map, ColumnEncoding::Utf16) {
+                    region.extend([
+                        ("startLine", (start.line + 1).into()),
+                        ("startColumn", (start.col + 1).into()),
+                        ("endLine", (end.line + 1).into()),
+                        ("endColumn", (end.col + 1).into()),
+                    ]);
+                }
+                region.extend([
+                    ("byteOffset", usize::from(label.span.start).into()),
+                    (
+                        "byteLength",
+                        usize::from(label.span.end).saturating_sub(usize::from(label.span.start)).into(),
+                    ),
+                ]);
+                let mut fields = vec![(
+                    "physicalLocation",
+                    Json::object([
+                        (
+                            "artifactLocation",
+                            match map.get(label.span.file) {
+                                Some(file) => Json::object([("uri", file.name().into())]),
+                                None => Json::object([(
+                                    "description",
+                                    Json::object([("text", format!("file {}", label.span.file).as_str().into())]),
+                                )]),
+                            },
+                        ),
+                        ("region", Json::Object(region)),
+                    ]),
+                )];
+                if !label.message.is_empty() {
+                    fields.push((
+                        "message",
+                        Json::object([("text", label.message.as_str().into())]),
+                    ));
+                }
+                Json::Object(fields)
+            };
+            let (primaries, secondaries): (Vec<&Label>, Vec<&Label>) = d
+                .labels
+                .iter()
+                .partition(|l| l.style == LabelStyle::Primary);
+
+            let mut text = d.message.clone();
+            for extra in d.notes.iter().chain(&d.help) {
+                text.push('\n');
+                text.push_str(extra);
+            }
+            let mut fields = vec![];
+            if let Some(code) = d.code {
+                fields.push(("ruleId", code.into()));
+            }
+            fields.push((
+                "level",
+                match d.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                    Severity::Note | Severity::Help => "note",
+                }
+                .into(),
+            ));
+            fields.push(("message", Json::object([("text", text.as_str().into())])));
+            fields.push((
+                "locations",
+                Json::Array(primaries.into_iter().map(location).collect()),
+            ));
+            if !secondaries.is_empty() {
+                fields.push((
+                    "relatedLocations",
+                    Json::Array(secondaries.into_iter().map(location).collect()),
+                ));
+            }
+            Json::Object(fields)
+        });
+
+        Json::object([
+            ("$schema", "https://json.schemastore.org/sarif-2.1.0.json".into()),
+            ("version", "2.1.0".into()),
+            (
+                "runs",
+                Json::Array(vec![Json::object([
+                    (
+                        "tool",
+                        Json::object([(
+                            "driver",
+                            Json::object([
+                                ("name", tool.into()),
+                                ("rules", Json::Array(rules.collect())),
+                            ]),
+                        )]),
+                    ),
+                    ("results", Json::Array(results.collect())),
+                ])]),
+            ),
+        ])
+        .to_pretty()
+    }
+}
+
+/// Returns the file of `label`, if it's in `map` and the label is within its bounds.
+fn locate<'m>(map: &'m SourceMap, label: &Label) -> Result<&'m SourceFile, SpanError> {
+    let file = map.get(label.span.file).ok_or(SpanError::UnknownFile(label.span.file))?;
+    label.span.slice(file.source())?;
+    Ok(file)
+}
+
+/// A label converted to lines and columns (0-based, counted in chars).
+///
+/// `end_col` is exclusive, and always greater than `start_col` when the label is on a single line.
+#[derive(Debug, Clone, Copy)]
+struct Marked<'d> {
+    label: &'d Label,
+    start_line: usize,
+    start_col: usize,
+    end_line: usize,
+    end_col: usize,
+}
+
+impl<'d> Marked<'d> {
+    fn new(file: &SourceFile, label: &'d Label) -> Self {
+        let (source, lines) = (file.source(), file.line_index());
+        let (start_line, start_col) = lines.line_col(source, label.span.start);
+        let start = usize::from(label.span.start);
+        // The end is computed from the last char, so a span ending right after a `\n` stays on its line
+        let last = source[start..usize::from(label.span.end)]
+            .chars()
+            .next_back()
+            .map_or(start, |c| usize::from(label.span.end) - c.len_utf8());
+        let (end_line, end_col) = lines.line_col(source, BytePos::from(last));
+        Marked {
+            label,
+            start_line: start_line - 1,
+            start_col: start_col - 1,
+            end_line: end_line - 1,
+            end_col,
+        }
+    }
+
+    fn is_multiline(&self) -> bool {
+        self.start_line != self.end_line
+    }
+
+    fn underline(&self) -> char {
+        match self.label.style {
+            LabelStyle::Primary => '^',
+            LabelStyle::Secondary => '-',
+        }
+    }
+
+    fn color(&self, severity: Severity) -> &'static str {
+        match self.label.style {
+            LabelStyle::Primary => severity.color(),
+            LabelStyle::Secondary => BLUE,
+        }
+    }
+}
+
+/// A line of output in which chars can be written at any column, each with its own style.
+#[derive(Default)]
+struct Canvas {
+    cells: Vec<(char, Option<&'static str>)>,
+}
+
+impl Canvas {
+    fn put(&mut self, col: usize, c: char, style: Option<&'static str>) {
+        if col >= self.cells.len() {
+            self.cells.resize(col + 1, (' ', None));
+        }
+        self.cells[col] = (c, style);
+    }
+
+    fn put_str(&mut self, col: usize, text: &str, style: Option<&'static str>) {
+        for (i, c) in text.chars().enumerate() {
+            self.put(col + i, c, style);
+        }
+    }
+
+    fn write_to(&self, out: &mut String, colored: bool) {
+        let len = self
+            .cells
+            .iter()
+            .rposition(|&(c, _)| c != ' ')
+            .map_or(0, |i| i + 1);
+        let mut cells = self.cells[..len].iter().peekable();
+        while let Some(&(c, style)) = cells.next() {
+            let mut run = String::from(c);
+            while let Some(&(c, _)) = cells.next_if(|&&(_, s)| s == style) {
+                run.push(c);
+            }
+            match style {
+                Some(style) if colored => out.push_str(&format!("\x1b[{}m{}\x1b[0m", style, run)),
+                _ => out.push_str(&run),
+            }
+        }
+        out.push('\n');
+    }
+}
+
+struct Renderer<'d> {
+    diagnostic: &'d Diagnostic,
+    map: &'d SourceMap,
+    /// The width of the line numbers
+    width: usize,
+    rows: Vec<Canvas>,
+}
+
+impl<'d> Renderer<'d> {
+    fn new(diagnostic: &'d Diagnostic, map: &'d SourceMap) -> Self {
+        let width = diagnostic
+            .labels
+            .iter()
+            .filter_map(|l| Some(Marked::new(locate(map, l).ok()?, l).end_line + 1))
+            .max()
+            .map_or(1, |line| line.to_string().len());
+        Renderer {
+            diagnostic,
+            map,
+            width,
+            rows: vec![],
+        }
+    }
+
+    fn render(mut self, colored: bool) -> String {
+        let d = self.diagnostic;
+        let mut header = Canvas::default();
+        let title = match &d.code {
+            Some(code) => format!("{}[{}]", d.severity, code),
+            None => d.severity.to_string(),
+        };
+        header.put_str(0, &title, Some(d.severity.color()));
+        header.put_str(
+            title.chars().count(),
+            &format!(": {}", d.message),
+            Some(BOLD),
+        );
+        self.rows.push(header);
+
+        // The file of the primary label comes first, then the others in the order of their labels
+        let mut files: Vec<(FileId, &SourceFile)> = vec![];
+        let primaries = d.labels.iter().filter(|l| l.style == LabelStyle::Primary);
+        let secondaries = d.labels.iter().filter(|l| l.style == LabelStyle::Secondary);
+        let mut unlocated = vec![];
+        for label in primaries.chain(secondaries) {
+            match locate(self.map, label) {
+                Ok(file) if !files.iter().any(|&(id, _)| id == label.span.file) => {
+                    files.push((label.span.file, file))
+                }
+                Ok(_) => {}
+                Err(e) if label.message.is_empty() => unlocated.push(format!("{}: {}", label.span, e)),
+                Err(e) => unlocated.push(format!("{} ({}): {}", label.span, label.message, e)),
+            }
+        }
+        for (i, &(id, file)) in files.iter().enumerate() {
+            self.render_file(id, file, i == 0);
+        }
+
+        if !unlocated.is_empty() || !d.notes.is_empty() || d.help.is_some() {
+            if !files.is_empty() {
+                self.rows.push(self.gutter_row());
+            }
+            let unlocated = unlocated.iter().map(|l| ("label", l));
+            let notes = unlocated.chain(d.notes.iter().map(|n| ("note", n)));
+            for (kind, text) in notes.chain(d.help.iter().map(|h| ("help", h))) {
+                let mut row = Canvas::default();
+                row.put(self.width + 1, '=', Some(BLUE));
+                row.put_str(self.width + 3, kind, Some(BOLD));
+                row.put_str(self.width + 3 + kind.len(), &format!(": {}", text), None);
+                self.rows.push(row);
+            }
+        }
+
+        let mut out = String::new();
+        for row in &self.rows {
+            row.write_to(&mut out, colored);
+        }
+        out
+    }
+
+    /// An empty row with only the `|` separating the line numbers from the snippets
+    fn gutter_row(&self) -> Canvas {
+        let mut row = Canvas::default();
+        row.put(self.width + 1, '|', Some(BLUE));
+        row
+    }
+
+    /// Renders the labels of `file` which can be located, there's at least one of them
+    fn render_file(&mut self, id: FileId, file: &SourceFile, first: bool) {
+        let d = self.diagnostic;
+        let marks: Vec<Marked> = d
+            .labels
+            .iter()
+            .filter(|l| l.span.file == id && locate(self.map, l).is_ok())
+            .map(|l| Marked::new(file, l))
+            .collect();
+        let location = marks
+            .iter()
+            .find(|m| m.label.style == LabelStyle::Primary)
+            .unwrap_or(&marks[0]);
+        if !first {
+            self.rows.push(self.gutter_row());
+        }
+        let mut row = Canvas::default();
+        row.put_str(self.width, if first { "--> " } else { "::: " }, Some(BLUE));
+        row.put_str(
+            self.width + 4,
+            &format!(
+                "{}:{}:{}",
+                file.name(),
+                location.start_line + 1,
+                location.start_col + 1
+            ),
+            None,
+        );
+        self.rows.push(row);
+        self.rows.push(self.gutter_row());
+
+        let mut multi: Vec<Marked> = marks.iter().copied().filter(Marked::is_multiline).collect();
+        multi.sort_by_key(|m| (m.start_line, m.start_col));
+        let gutter_width = if multi.is_empty() { 0 } else { multi.len() + 1 };
+        let text_col = self.width + 3 + gutter_width;
+
+        // The lines of single-line labels, and the first two and last two lines of multi-line ones
+        let mut lines = BTreeSet::new();
+        for m in &marks {
+            lines.insert(m.start_line);
+            lines.insert((m.start_line + 1).min(m.end_line));
+            lines.insert(m.end_line.saturating_sub(1).max(m.start_line));
+            lines.insert(m.end_line);
+        }
+        // Hiding a single line behind `...` wouldn't save anything
+        let gaps: Vec<usize> = lines
+            .iter()
+            .zip(lines.iter().skip(1))
+            .filter(|&(a, b)| b - a == 2)
+            .map(|(a, _)| a + 1)
+            .collect();
+        lines.extend(gaps);
+
+        let mut active = vec![false; multi.len()];
+        let mut previous = None;
+        for &line in &lines {
+            if previous.is_some_and(|p| line > p + 1) {
+                let mut row = Canvas::default();
+                row.put_str(0, "...", Some(BLUE));
+                self.put_gutters(&mut row, &multi, &active);
+                self.rows.push(row);
+            }
+            previous = Some(line);
+            let text = file
+                .line_index()
+                .line_text(file.source(), line)
+                .replace('\t', " ");
+
+            // The labels starting at the beginning of the line are drawn with a `/` instead of a row of `_`
+            let mut row = self.gutter_row();
+            row.put_str(0, &format!("{:<1$}", line + 1, self.width), Some(BLUE));
+            self.put_gutters(&mut row, &multi, &active);
+            let mut starts = vec![];
+            for (i, m) in multi
+                .iter()
+                .enumerate()
+                .filter(|(_, m)| m.start_line == line)
+            {
+                if text.chars().take(m.start_col).all(char::is_whitespace) {
+                    row.put(self.width + 3 + i, '/', Some(m.color(d.severity)));
+                    active[i] = true;
+                } else {
+                    starts.push(i);
+                }
+            }
+            row.put_str(text_col, &text, None);
+            self.rows.push(row);
+
+            for i in starts {
+                let m = multi[i];
+                let mut row = self.gutter_row();
+                self.put_gutters(&mut row, &multi, &active);
+                for col in self.width + 3 + i + 1..text_col + m.start_col {
+                    row.put(col, '_', Some(m.color(d.severity)));
+                }
+                row.put(
+                    text_col + m.start_col,
+                    m.underline(),
+                    Some(m.color(d.severity)),
+                );
+                self.rows.push(row);
+                active[i] = true;
+            }
+
+            let mut singles: Vec<Marked> = marks
+                .iter()
+                .copied()
+                .filter(|m| !m.is_multiline() && m.start_line == line)
+                .collect();
+            singles.sort_by_key(|m| (m.start_col, m.end_col));
+            self.render_singles(&singles, &multi, &active, text_col);
+
+            for i in 0..multi.len() {
+                let m = multi[i];
+                if m.end_line != line {
+                    continue;
+                }
+                let mut row = self.gutter_row();
+                self.put_gutters(&mut row, &multi, &active);
+                let last = text_col + m.end_col - 1;
+                for col in self.width + 3 + i + 1..last {
+                    row.put(col, '_', Some(m.color(d.severity)));
+                }
+                row.put(last, m.underline(), Some(m.color(d.severity)));
+                if !m.label.message.is_empty() {
+                    row.put_str(last + 2, &m.label.message, Some(m.color(d.severity)));
+                }
+                self.rows.push(row);
+                active[i] = false;
+            }
+        }
+    }
+
+    /// Draws the underlines of the labels on a single line, then their messages
+    fn render_singles(
+        &mut self,
+        singles: &[Marked],
+        multi: &[Marked],
+        active: &[bool],
+        text_col: usize,
+    ) {
+        let severity = self.diagnostic.severity;
+        if singles.is_empty() {
+            return;
+        }
+        let mut row = self.gutter_row();
+        self.put_gutters(&mut row, multi, active);
+        // Primary labels are drawn last so they stay visible when labels overlap
+        let secondaries = singles
+            .iter()
+            .filter(|m| m.label.style == LabelStyle::Secondary);
+        let primaries = singles
+            .iter()
+            .filter(|m| m.label.style == LabelStyle::Primary);
+        for m in secondaries.chain(primaries) {
+            for col in m.start_col..m.end_col.max(m.start_col + 1) {
+                row.put(text_col + col, m.underline(), Some(m.color(severity)));
+            }
+        }
+        // The message of the rightmost label goes right after the underlines, the others below them
+        let (last, others) = singles.split_last().expect("`singles` isn't empty");
+        let end = singles
+            .iter()
+            .map(|m| m.end_col.max(m.start_col + 1))
+            .max()
+            .unwrap_or(0);
+        if !last.label.message.is_empty() {
+            row.put_str(
+                text_col + end + 1,
+                &last.label.message,
+                Some(last.color(severity)),
+            );
+        }
+        self.rows.push(row);
+
+        let pending: Vec<&Marked> = others
+            .iter()
+            .filter(|m| !m.label.message.is_empty())
+            .collect();
+        for (i, m) in pending.iter().enumerate().rev() {
+            let mut connector = self.gutter_row();
+            let mut message = self.gutter_row();
+            self.put_gutters(&mut connector, multi, active);
+            self.put_gutters(&mut message, multi, active);
+            for left in &pending[..=i] {
+                connector.put(text_col + left.start_col, '|', Some(left.color(severity)));
+            }
+            for left in &pending[..i] {
+                message.put(text_col + left.start_col, '|', Some(left.color(severity)));
+            }
+            message.put_str(
+                text_col + m.start_col,
+                &m.label.message,
+                Some(m.color(severity)),
+            );
+            self.rows.push(connector);
+            self.rows.push(message);
+        }
+    }
+
+    /// Draws the `|` of the multi-line labels going through the current row
+    fn put_gutters(&self, row: &mut Canvas, multi: &[Marked], active: &[bool]) {
+        for (i, m) in multi.iter().enumerate() {
+            if active[i] {
+                row.put(
+                    self.width + 3 + i,
+                    '|',
+                    Some(m.color(self.diagnostic.severity)),
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,55 @@
+/// A code identifying a kind of diagnostic, with the explanation shown by [`explain`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorCode {
+    /// The code itself, like `A0001`
+    pub code: &'static str,
+    /// A one-line summary of the error
+    pub title: &'static str,
+    /// The long-form explanation, in Markdown, with an erroneous example and how to fix it
+    pub explanation: &'static str,
+}
+
+macro_rules! error_codes {
+    ($($code:ident => $title:literal,)*) => {
+        $(
+            #[doc = concat!("`", stringify!($code), "`: ", $title)]
+            pub const $code: &str = stringify!($code);
+        )*
+
+        /// Every registered code, sorted
+        pub static ERROR_CODES: &[ErrorCode] = &[$(
+            ErrorCode {
+                code: $code,
+                title: $title,
+                explanation: include_str!(concat!("error_codes/", stringify!($code), ".md")),
+            },
+        )*];
+    };
+}
+
+error_codes! {
+    A0001 => "unterminated string",
+    A0002 => "unexpected character",
+    A0003 => "invalid literal",
+    A0004 => "identifier not following the naming convention",
+    A0005 => "unexpected token",
+}
+
+/// Returns the registered code `code`, `None` if it doesn't exist.
+pub fn lookup(code: &str) -> Option<&'static ErrorCode> {
+    ERROR_CODES.iter().find(|c| c.code.eq_ignore_ascii_case(code))
+}
+
+/// Returns the long-form explanation of `code`, like `rustc --explain`.
+///
+/// # Example
+///
+/// ```
+/// use atlas_core::utils::error_codes::explain;
+///
+/// assert!(explain("A0001").unwrap().starts_with("A string literal is never closed."));
+/// assert!(explain("A9999").is_none());
+/// ```
+pub fn explain(code: &str) -> Option<&'static str> {
+    lookup(code).map(|c| c.explanation)
+}
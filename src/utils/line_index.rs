@@ -0,0 +1,151 @@
+use crate::utils::span::BytePos;
+
+/// The unit in which the columns of a [`LineCol`] are counted.
+///
+/// The LSP counts in UTF-16 code units by default, while editors and terminals usually
+/// count in chars (UTF-32) and `BytePos` in bytes (UTF-8).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ColumnEncoding {
+    /// Columns are counted in bytes
+    Utf8,
+    /// Columns are counted in UTF-16 code units: chars outside of the BMP count for two
+    Utf16,
+    /// Columns are counted in chars
+    Utf32,
+}
+
+impl ColumnEncoding {
+    /// Returns the number of units `c` takes in this encoding
+    #[inline(always)]
+    pub fn len(self, c: char) -> usize {
+        match self {
+            ColumnEncoding::Utf8 => c.len_utf8(),
+            ColumnEncoding::Utf16 => c.len_utf16(),
+            ColumnEncoding::Utf32 => 1,
+        }
+    }
+}
+
+/// A position as a line and a column, both 0-based like in the LSP.
+///
+/// The unit of `col` depends on the [`ColumnEncoding`] used to compute it.
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Eq, Ord, Hash, Default)]
+pub struct LineCol {
+    /// The line (0-based)
+    pub line: usize,
+    /// The column (0-based)
+    pub col: usize,
+}
+
+/// `LineIndex` stores the byte position of the start of every line of a source.
+///
+/// It's built once when a file is added to a [`SourceMap`](crate::utils::source_map::SourceMap),
+/// so finding the line of a position is a binary search instead of a scan of the whole file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    line_starts: Vec<BytePos>,
+}
+
+impl LineIndex {
+    /// Builds the index of `source`
+    pub fn new(source: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .map(BytePos::from)
+            .collect();
+        Self { line_starts }
+    }
+
+    /// Returns the number of lines. A source always has at least one (possibly empty) line.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Returns the position of the start of `line` (0-based), `None` if there is no such line.
+    pub fn line_start(&self, line: usize) -> Option<BytePos> {
+        self.line_starts.get(line).copied()
+    }
+
+    /// Returns the line (0-based) containing `pos`.
+    ///
+    /// A position on a `\n` belongs to the line the `\n` ends.
+    pub fn line_of(&self, pos: BytePos) -> usize {
+        match self.line_starts.binary_search(&pos) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        }
+    }
+
+    /// Returns the text of `line` (0-based) in `source`, without its line ending.
+    ///
+    /// # Panics
+    /// Panics if `source` isn't the text the index was built from.
+    pub fn line_text<'src>(&self, source: &'src str, line: usize) -> &'src str {
+        let start = usize::from(self.line_starts[line]);
+        let end = self
+            .line_starts
+            .get(line + 1)
+            .map_or(source.len(), |&next| usize::from(next) - 1);
+        source[start..end]
+            .strip_suffix('\r')
+            .unwrap_or(&source[start..end])
+    }
+
+    /// Converts `pos` to a line and a column counted in `encoding`.
+    ///
+    /// # Panics
+    /// Panics if `source` isn't the text the index was built from, or if `pos` is past its end
+    /// or isn't on a char boundary.
+    pub fn to_line_col(&self, source: &str, pos: BytePos, encoding: ColumnEncoding) -> LineCol {
+        let line = self.line_of(pos);
+        let start = usize::from(self.line_starts[line]);
+        let prefix = &source[start..usize::from(pos)];
+        let col = match encoding {
+            ColumnEncoding::Utf8 => prefix.len(),
+            _ => prefix.chars().map(|c| encoding.len(c)).sum(),
+        };
+        LineCol { line, col }
+    }
+
+    /// Converts a line and a column counted in `encoding` back to a `BytePos`.
+    ///
+    /// The column can go up to the end of the line (line ending excluded).
+    ///
+    /// # Returns
+    /// - `Some(pos)`: The position in bytes.
+    /// - `None`: If the line doesn't exist, the column is past the end of the line,
+    ///   or it points inside a char (e.g. between the two halves of a UTF-16 surrogate pair).
+    ///
+    /// # Panics
+    /// Panics if `source` isn't the text the index was built from.
+    pub fn to_byte_pos(
+        &self,
+        source: &str,
+        line_col: LineCol,
+        encoding: ColumnEncoding,
+    ) -> Option<BytePos> {
+        let start = usize::from(self.line_start(line_col.line)?);
+        let text = self.line_text(source, line_col.line);
+        let mut units = 0;
+        for (offset, c) in text.char_indices() {
+            if units == line_col.col {
+                return Some(BytePos::from(start + offset));
+            }
+            units += encoding.len(c);
+            if units > line_col.col {
+                return None;
+            }
+        }
+        (units == line_col.col).then(|| BytePos::from(start + text.len()))
+    }
+
+    /// Returns the line and the column of `pos`, both 1-based, the column being counted in chars.
+    ///
+    /// # Panics
+    /// Panics if `source` isn't the text the index was built from, or if `pos` is past its end
+    /// or isn't on a char boundary.
+    pub fn line_col(&self, source: &str, pos: BytePos) -> (usize, usize) {
+        let LineCol { line, col } = self.to_line_col(source, pos, ColumnEncoding::Utf32);
+        (line + 1, col + 1)
+    }
+}
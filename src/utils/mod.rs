@@ -1,5 +1,17 @@
-/// Contains the case trait and its implementations for `String` and `&str`
+/// Contains the `Case` trait, checking, detecting and converting the naming convention of identifiers, and `CaseStyle`
 pub mod case;
+/// Contains the `Diagnostic` type and its renderer
+pub mod diagnostic;
+/// Contains the registry of the codes of the diagnostics and their explanations
+pub mod error_codes;
+// The tiny JSON writer used for the machine-readable diagnostics
+mod json;
+/// Contains the `LineIndex`, mapping byte positions to lines and columns
+pub mod line_index;
+/// Contains the lint pass checking the naming convention of the declared identifiers
+pub mod naming;
+/// Contains the `SourceMap`, owning the name and content of every file, and the `FileId` referring to them
+pub mod source_map;
 /// Contains all the definition and implementations for the enums/traits/structs for the Span related stuff.
 ///
 /// (`BytePos`, `Span`)
@@ -0,0 +1,145 @@
+use crate::utils::{
+    case::CaseStyle,
+    diagnostic::{Diagnostic, Label},
+    error_codes::A0004,
+    span::{Span, Spanned},
+};
+
+/// What a token is, as far as the naming lints are concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameTokenKind<'t> {
+    /// A keyword, with its text
+    Keyword(&'t str),
+    /// An identifier, with its text
+    Identifier(&'t str),
+    /// Whitespace and line endings, skipped when looking for the identifier after a keyword
+    Trivia,
+    /// Any other token
+    Other,
+}
+
+/// Implemented by the tokens the naming lints can walk.
+///
+/// The `Token` generated by [`tokens!`](crate::tokens) already implements it.
+pub trait NameToken: Spanned {
+    /// Returns what the token is
+    fn name_token(&self) -> NameTokenKind<'_>;
+}
+
+impl<T: NameToken + ?Sized> NameToken for &T {
+    #[inline(always)]
+    fn name_token(&self) -> NameTokenKind<'_> {
+        (**self).name_token()
+    }
+}
+
+/// A naming rule: the identifier following `keyword` must be written in `style`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamingRule {
+    /// The keyword introducing the identifier, like `fn`
+    pub keyword: String,
+    /// What the identifier names, used in the message, like `function`
+    pub item: String,
+    /// The convention the identifier must follow
+    pub style: CaseStyle,
+}
+
+/// A lint pass checking the naming convention of the identifiers declared in a token stream.
+///
+/// Each [`NamingRule`] applies to the identifier right after its keyword (trivia skipped),
+/// and every identifier breaking its rule is reported as a warning, with the name it should
+/// have as a suggestion.
+///
+/// # Example
+///
+/// ```
+/// use atlas_core::utils::{case::CaseStyle, naming::{NameTokenKind, NamingLints}};
+/// # use atlas_core::utils::{naming::NameToken, span::{Span, Spanned}};
+/// # struct Tok(NameTokenKind<'static>, Span);
+/// # impl Spanned for Tok { fn span(&self) -> Span { self.1 } }
+/// # impl NameToken for Tok { fn name_token(&self) -> NameTokenKind<'_> { self.0 } }
+/// # let tokens = [
+/// #     Tok(NameTokenKind::Keyword("fn"), Span::empty()),
+/// #     Tok(NameTokenKind::Identifier("doThings"), Span::empty()),
+/// # ];
+///
+/// // `tokens`: `fn doThings`
+/// let lints = NamingLints::new().with_rule("fn", "function", CaseStyle::Snake);
+/// let diagnostics = lints.check(&tokens);
+/// assert_eq!(diagnostics[0].message, "function `doThings` should have a snake_case name");
+/// assert_eq!(diagnostics[0].help.as_deref(), Some("rename it to `do_things`"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamingLints {
+    rules: Vec<NamingRule>,
+}
+
+impl NamingLints {
+    /// Creates a pass without any rule
+    pub fn new() -> Self {
+        Self { rules: vec![] }
+    }
+
+    /// Adds a rule, replacing the one already set for `keyword`
+    pub fn with_rule(mut self, keyword: &str, item: &str, style: CaseStyle) -> Self {
+        self.rules.retain(|r| r.keyword != keyword);
+        self.rules.push(NamingRule {
+            keyword: keyword.to_owned(),
+            item: item.to_owned(),
+            style,
+        });
+        self
+    }
+
+    /// Returns the rules of the pass
+    pub fn rules(&self) -> &[NamingRule] {
+        &self.rules
+    }
+
+    /// Walks `tokens` and returns a warning for every identifier breaking its rule, in order.
+    pub fn check<T: NameToken>(&self, tokens: &[T]) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+        let mut rule: Option<&NamingRule> = None;
+        for token in tokens {
+            match token.name_token() {
+                NameTokenKind::Trivia => continue,
+                NameTokenKind::Identifier(name) => {
+                    if let Some(rule) = rule.filter(|r| !r.style.matches(name)) {
+                        diagnostics.push(naming_diagnostic(rule, name, token.span()));
+                    }
+                    rule = None;
+                }
+                NameTokenKind::Keyword(keyword) => {
+                    rule = self.rules.iter().find(|r| r.keyword == keyword);
+                }
+                NameTokenKind::Other => rule = None,
+            }
+        }
+        diagnostics
+    }
+}
+
+impl Default for NamingLints {
+    /// The usual conventions: PascalCase types, SHOUTY_SNAKE_CASE constants,
+    /// snake_case functions and variables
+    fn default() -> Self {
+        Self::new()
+            .with_rule("struct", "type", CaseStyle::Pascal)
+            .with_rule("enum", "type", CaseStyle::Pascal)
+            .with_rule("type", "type", CaseStyle::Pascal)
+            .with_rule("const", "constant", CaseStyle::ShoutySnake)
+            .with_rule("fn", "function", CaseStyle::Snake)
+            .with_rule("let", "variable", CaseStyle::Snake)
+    }
+}
+
+fn naming_diagnostic(rule: &NamingRule, name: &str, span: Span) -> Diagnostic {
+    let suggestion = rule.style.convert(name);
+    Diagnostic::warning(format!(
+        "{} `{}` should have a {} name",
+        rule.item, name, rule.style
+    ))
+    .with_code(A0004)
+    .with_label(Label::primary(span, format!("not in {}", rule.style)))
+    .with_help(format!("rename it to `{}`", suggestion))
+}
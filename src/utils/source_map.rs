@@ -0,0 +1,172 @@
+use crate::utils::{
+    line_index::{ColumnEncoding, LineCol, LineIndex},
+    span::{BytePos, ExpansionId, ExpansionTable, Span, SpanError},
+};
+use core::fmt;
+use std::{io, path::Path};
+
+/// Identifies a file registered in a [`SourceMap`].
+///
+/// It's a compact handle (a `u32`) stored in every `Span` instead of the path of the file.
+/// `FileId::default()` refers to the first file added to a `SourceMap`.
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Eq, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileId(pub(crate) u32);
+
+impl FileId {
+    /// Returns the index of the file in its `SourceMap`
+    #[inline(always)]
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl fmt::Display for FileId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// A file owned by a [`SourceMap`]: its name, its content and the index of its lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    name: String,
+    source: String,
+    lines: LineIndex,
+}
+
+impl SourceFile {
+    /// Returns the name of the file (usually its path, or something like `<stdin>` for virtual files)
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the content of the file
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns the index of the lines of the file, computed when it was added to the map
+    pub fn line_index(&self) -> &LineIndex {
+        &self.lines
+    }
+
+    /// Converts `pos` to a 0-based line and a column counted in `encoding`.
+    ///
+    /// # Panics
+    /// Panics if `pos` is past the end of the file or isn't on a char boundary.
+    pub fn to_line_col(&self, pos: BytePos, encoding: ColumnEncoding) -> LineCol {
+        self.lines.to_line_col(&self.source, pos, encoding)
+    }
+
+    /// Converts a 0-based line and a column counted in `encoding` back to a `BytePos`,
+    /// `None` if it doesn't point to a char boundary of the file.
+    pub fn to_byte_pos(&self, line_col: LineCol, encoding: ColumnEncoding) -> Option<BytePos> {
+        self.lines.to_byte_pos(&self.source, line_col, encoding)
+    }
+}
+
+/// `SourceMap` owns the names and contents of every source file, and hands out a [`FileId`] for each.
+///
+/// Files don't need to exist on disk: anything given to [`SourceMap::add`] (the REPL input,
+/// a buffer of an editor, ...) gets its own `FileId`.
+///
+/// # Example
+///
+/// ```
+/// use atlas_core::utils::source_map::SourceMap;
+///
+/// let mut map = SourceMap::new();
+/// let id = map.add("<stdin>", "let x = 5;");
+/// assert_eq!(map.name(id), "<stdin>");
+/// assert_eq!(map.source(id), "let x = 5;");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+    expansions: ExpansionTable,
+}
+
+impl SourceMap {
+    /// Creates an empty `SourceMap`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file to the map and returns its `FileId`
+    pub fn add(&mut self, name: impl Into<String>, source: impl Into<String>) -> FileId {
+        let id = FileId(u32::try_from(self.files.len()).expect("Too many files in the SourceMap"));
+        let source = source.into();
+        self.files.push(SourceFile {
+            name: name.into(),
+            lines: LineIndex::new(&source),
+            source,
+        });
+        id
+    }
+
+    /// Reads the file at `path` from the disk and adds it to the map
+    pub fn load(&mut self, path: impl AsRef<Path>) -> io::Result<FileId> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)?;
+        Ok(self.add(path.display().to_string(), source))
+    }
+
+    /// Returns the file with the given id, `None` if it doesn't belong to this map
+    pub fn get(&self, id: FileId) -> Option<&SourceFile> {
+        self.files.get(id.index())
+    }
+
+    /// Returns the name of a file.
+    ///
+    /// # Panics
+    /// Panics if the file doesn't belong to this map.
+    pub fn name(&self, id: FileId) -> &str {
+        self.files[id.index()].name()
+    }
+
+    /// Returns the content of a file.
+    ///
+    /// # Panics
+    /// Panics if the file doesn't belong to this map.
+    pub fn source(&self, id: FileId) -> &str {
+        self.files[id.index()].source()
+    }
+
+    /// Returns the text covered by `span`.
+    ///
+    /// Fails if the file of the span isn't in the map, or if the span is out of its bounds
+    /// or not on char boundaries.
+    pub fn snippet(&self, span: Span) -> Result<&str, SpanError> {
+        let file = self.get(span.file).ok_or(SpanError::UnknownFile(span.file))?;
+        span.slice(file.source())
+    }
+
+    /// Records an expansion of the macro `macro_name`, see [`ExpansionTable::add`]
+    pub fn add_expansion(&mut self, macro_name: impl Into<String>, call_site: Span, def_site: Span) -> ExpansionId {
+        self.expansions.add(macro_name, call_site, def_site)
+    }
+
+    /// Returns the macro expansions of the spans of the files
+    pub fn expansions(&self) -> &ExpansionTable {
+        &self.expansions
+    }
+
+    /// Returns the number of files in the map
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Returns `true` if the map doesn't contain any file
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Iterates over the files of the map with their id
+    pub fn files(&self) -> impl Iterator<Item = (FileId, &SourceFile)> {
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (FileId(i as u32), f))
+    }
+}
@@ -1,7 +1,15 @@
-use core::fmt;
+use crate::utils::{
+    line_index::{ColumnEncoding, LineCol},
+    source_map::{FileId, SourceMap},
+};
+use core::{
+    fmt,
+    ops::{Deref, DerefMut, Range},
+};
 
 /// Represents a position in bytes within a source file.
-#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Eq, Ord, Default)]
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Eq, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BytePos(usize);
 
 impl BytePos {
@@ -37,25 +45,53 @@ impl From<BytePos> for usize {
 }
 
 /// Represents a span in a source file, defined by a start and end byte position.
-#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Eq, Ord, Default)]
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Eq, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Span {
     /// The position of character at the start of the span
     pub start: BytePos,
     /// The position of character at the end of the span
     pub end: BytePos,
-    /// The source file path
-    pub path: &'static str,
+    /// The file of the `SourceMap` the span points into
+    pub file: FileId,
+    /// The macro expansion the span comes from, `ExpansionId::ROOT` if it was written as is
+    pub expansion: ExpansionId,
 }
 
 impl Span {
+    /// Creates a new `Span`, checking it lies in a source of `source_len` bytes.
+    ///
+    /// # Returns
+    /// - `Ok(span)`: The span going from `start` to `end`.
+    /// - `Err(SpanError::Inverted)`: If `start` is after `end`.
+    /// - `Err(SpanError::OutOfBounds)`: If `end` is past the end of the source.
+    pub fn new(start: usize, end: usize, file: FileId, source_len: usize) -> Result<Self, SpanError> {
+        if start > end {
+            return Err(SpanError::Inverted { start, end });
+        }
+        if end > source_len {
+            return Err(SpanError::OutOfBounds {
+                end,
+                len: source_len,
+            });
+        }
+        Ok(Span {
+            start: BytePos(start),
+            end: BytePos(end),
+            file,
+            expansion: ExpansionId::ROOT,
+        })
+    }
+
     /// Creates a new `Span` without bounds checking.
     /// # Safety
     /// It's the caller's responsibility to ensure that `start` and `end` are valid
-    pub unsafe fn new_unchecked(start: usize, end: usize, path: &'static str) -> Self {
+    pub unsafe fn new_unchecked(start: usize, end: usize, file: FileId) -> Self {
         Span {
             start: BytePos(start),
             end: BytePos(end),
-            path,
+            file,
+            expansion: ExpansionId::ROOT,
         }
     }
 
@@ -65,70 +101,495 @@ impl Span {
         Span {
             start: BytePos(0),
             end: BytePos(0),
-            path: "",
+            file: FileId(0),
+            expansion: ExpansionId::ROOT,
         }
     }
 
     /// Combines two spans to create a new span that encompasses both.
+    ///
+    /// # Panics
+    /// Panics if the spans come from different files, see [`Span::try_union`] for a fallible version.
     pub fn union_span(self, other: Self) -> Self {
+        match self.try_union(other) {
+            Ok(span) => span,
+            Err(e) => panic!("Cannot union spans: {}", e),
+        }
+    }
+
+    /// Combines two spans to create a new span that encompasses both.
+    ///
+    /// The result keeps the macro expansion of `self`.
+    ///
+    /// # Returns
+    /// - `Ok(span)`: The span going from the first start to the last end.
+    /// - `Err(SpanError::DifferentFiles)`: If the spans come from different files.
+    pub fn try_union(self, other: Self) -> Result<Self, SpanError> {
         use std::cmp;
-        if self.path != other.path {
-            panic!(
-                "Cannot union spans from different files: {} and {}",
-                self.path, other.path
-            );
+        if self.file != other.file {
+            return Err(SpanError::DifferentFiles(self.file, other.file));
         }
-        Span {
+        Ok(Span {
             start: cmp::min(self.start, other.start),
             end: cmp::max(self.end, other.end),
-            path: self.path,
+            ..self
+        })
+    }
+
+    /// Returns the span going from the start of `self` to the end of `other`
+    /// (e.g. from the `(` to the `)` of a call).
+    ///
+    /// If `other` ends before `self` starts, the span is empty at the start of `self`.
+    ///
+    /// # Panics
+    /// Panics if the spans come from different files.
+    pub fn to(self, other: Self) -> Self {
+        if self.file != other.file {
+            panic!("Cannot join spans: {}", SpanError::DifferentFiles(self.file, other.file));
+        }
+        Span {
+            start: self.start,
+            end: other.end.max(self.start),
+            ..self
         }
     }
 
-    /// Retrieves line information associated with the span.
-    pub fn get_line_info(&self) -> LineInformation {
-        let start_byte = self.start.0;
-        let end_byte = self.end.0;
-        let content = std::fs::read_to_string(self.path).expect("Unable to read file");
-        // Find the start and end of the line containing the span's start position
-        let line_start = content[..start_byte]
-            .rfind('\n')
-            .map(|idx| idx + 1)
-            .unwrap_or(0);
-        let line_end = content[end_byte..]
-            .find('\n')
-            .map(|idx| end_byte + idx)
-            .unwrap_or(end_byte);
+    /// Returns the length of the span in bytes
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.end.0 - self.start.0
+    }
+
+    /// Returns `true` if the span doesn't cover any byte
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
 
-        let line_text = content[line_start..=(line_end - 1)].to_owned();
-        let line_number = content[..start_byte].chars().filter(|&c| c == '\n').count() + 1;
-        let column_number = start_byte - line_start + 1;
+    /// Returns `true` if `pos` is in the span, the end being excluded
+    #[inline]
+    pub fn contains(&self, pos: BytePos) -> bool {
+        self.start <= pos && pos < self.end
+    }
 
-        LineInformation::new(line_number, column_number, line_text)
+    /// Returns `true` if both spans are in the same file and share at least one byte.
+    ///
+    /// Spans that only touch (one ends where the other starts) don't intersect.
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.file == other.file && self.start < other.end && other.start < self.end
+    }
+
+    /// Shrinks the span by `start` bytes at its start and `end` bytes at its end
+    /// (e.g. `shrink(1, 1)` to get the content of a string without its quotes).
+    ///
+    /// Returns `None` if the span is too short to be shrunk that much.
+    pub fn shrink(self, start: usize, end: usize) -> Option<Self> {
+        if start.checked_add(end)? > self.len() {
+            return None;
+        }
+        Some(Span {
+            start: self.start.shift_by(start),
+            end: BytePos(self.end.0 - end),
+            ..self
+        })
+    }
+
+    /// Splits the span in two at `offset` bytes from its start.
+    ///
+    /// Returns `None` if `offset` is past the end of the span.
+    pub fn split_at(self, offset: usize) -> Option<(Self, Self)> {
+        if offset > self.len() {
+            return None;
+        }
+        let middle = self.start.shift_by(offset);
+        Some((
+            Span {
+                end: middle,
+                ..self
+            },
+            Span {
+                start: middle,
+                ..self
+            },
+        ))
+    }
+
+    /// Returns the part of the span covered by `range`, an offset in bytes from its start.
+    ///
+    /// Returns `None` if the range is inverted or goes past the end of the span.
+    pub fn subspan(self, range: Range<usize>) -> Option<Self> {
+        if range.start > range.end || range.end > self.len() {
+            return None;
+        }
+        Some(Span {
+            start: self.start.shift_by(range.start),
+            end: self.start.shift_by(range.end),
+            ..self
+        })
+    }
+
+    /// Returns the same span, marked as produced by the macro expansion `expansion`
+    pub fn with_expansion(self, expansion: ExpansionId) -> Self {
+        Span { expansion, ..self }
+    }
+
+    /// Returns `true` if the span was produced by a macro expansion
+    pub fn is_from_expansion(&self) -> bool {
+        !self.expansion.is_root()
+    }
+
+    /// Returns the invocation of the macro the span comes from, `None` if it wasn't produced by a macro
+    pub fn call_site(&self, table: &ExpansionTable) -> Option<Span> {
+        table.get(self.expansion).map(|e| e.call_site)
+    }
+
+    /// Returns the definition of the macro the span comes from, `None` if it wasn't produced by a macro
+    pub fn def_site(&self, table: &ExpansionTable) -> Option<Span> {
+        table.get(self.expansion).map(|e| e.def_site)
+    }
+
+    /// Iterates over the expansions the span comes from, from the innermost to the outermost.
+    ///
+    /// If a macro invokes another macro, the first item is the expansion of the inner macro,
+    /// whose call site is in the body of the outer macro.
+    pub fn macro_backtrace<'t>(&self, table: &'t ExpansionTable) -> impl Iterator<Item = &'t Expansion> {
+        let mut current = self.expansion;
+        std::iter::from_fn(move || {
+            let expansion = table.get(current)?;
+            current = expansion.call_site.expansion;
+            Some(expansion)
+        })
+    }
+
+    /// Returns the outermost invocation the span comes from, the span itself if it wasn't produced by a macro.
+    ///
+    /// That's the span written by the user, which is the one to point at in the source.
+    pub fn source_callsite(&self, table: &ExpansionTable) -> Span {
+        self.macro_backtrace(table).last().map_or(*self, |e| e.call_site)
+    }
+
+    /// Returns the text covered by the span in `source`.
+    ///
+    /// # Returns
+    /// - `Ok(text)`: The text of the span.
+    /// - `Err(SpanError::Inverted)`: If the span starts after its end.
+    /// - `Err(SpanError::OutOfBounds)`: If the span goes past the end of `source`.
+    /// - `Err(SpanError::NotCharBoundary)`: If the span starts or ends in the middle of a char.
+    pub fn slice<'src>(&self, source: &'src str) -> Result<&'src str, SpanError> {
+        let (start, end) = (self.start.0, self.end.0);
+        if start > end {
+            return Err(SpanError::Inverted { start, end });
+        }
+        if end > source.len() {
+            return Err(SpanError::OutOfBounds {
+                end,
+                len: source.len(),
+            });
+        }
+        for pos in [start, end] {
+            if !source.is_char_boundary(pos) {
+                return Err(SpanError::NotCharBoundary(pos));
+            }
+        }
+        Ok(&source[start..end])
+    }
+
+    /// Retrieves the lines and columns of the start and the end of the span.
+    ///
+    /// The lookup goes through the line index of the file in `map`, the filesystem is never touched.
+    ///
+    /// # Returns
+    /// - `Ok(info)`: The lines and columns of the span.
+    /// - `Err(SpanError::UnknownFile)`: If the file of the span doesn't belong to `map`.
+    /// - `Err(_)`: If the span can't be sliced from its file, see [`Span::slice`].
+    pub fn get_line_info(&self, map: &SourceMap) -> Result<LineInformation, SpanError> {
+        let file = map.get(self.file).ok_or(SpanError::UnknownFile(self.file))?;
+        let (source, lines) = (file.source(), file.line_index());
+        self.slice(source)?;
+        let (start_line, start_column) = lines.line_col(source, self.start);
+        let (end_line, end_column) = lines.line_col(source, self.end);
+        let line_text = (start_line..=end_line)
+            .map(|line| lines.line_text(source, line - 1))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Ok(LineInformation {
+            start_line,
+            start_column,
+            end_line,
+            end_column,
+            line_text,
+        })
+    }
+
+    /// Converts the start and the end of the span to 0-based lines and columns counted in `encoding`.
+    ///
+    /// Use `ColumnEncoding::Utf16` to build the `Range` of an LSP message.
+    ///
+    /// # Returns
+    /// - `Ok((start, end))`: The lines and columns of the start and the end of the span.
+    /// - `Err(SpanError::UnknownFile)`: If the file of the span doesn't belong to `map`.
+    /// - `Err(_)`: If the span can't be sliced from its file, see [`Span::slice`].
+    pub fn to_line_cols(&self, map: &SourceMap, encoding: ColumnEncoding) -> Result<(LineCol, LineCol), SpanError> {
+        let file = map.get(self.file).ok_or(SpanError::UnknownFile(self.file))?;
+        self.slice(file.source())?;
+        Ok((file.to_line_col(self.start, encoding), file.to_line_col(self.end, encoding)))
     }
 }
 
 impl fmt::Display for Span {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "[{}:{} in \"{}\"]", self.start, self.end, self.path)
+        write!(f, "[{}:{} in file {}]", self.start, self.end, self.file)
     }
 }
 
-/// WARNING: LineInformation need a huge rework as it's not working well at all
-pub struct LineInformation {
-    pub line_number: usize,
-    pub column_number: usize,
-    pub line_text: String,
+/// Identifies a macro expansion registered in an [`ExpansionTable`].
+///
+/// Every `Span` produced by a macro expansion carries the id of that expansion, which remembers
+/// where the macro was invoked and where it's defined.
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Eq, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExpansionId(u32);
+
+impl ExpansionId {
+    /// The id of the spans that don't come from any macro expansion
+    pub const ROOT: ExpansionId = ExpansionId(0);
+
+    /// Returns `true` for the spans that don't come from any macro expansion
+    #[inline(always)]
+    pub fn is_root(self) -> bool {
+        self == Self::ROOT
+    }
 }
 
-impl LineInformation {
-    pub fn new(line_number: usize, column_number: usize, line_text: String) -> Self {
-        LineInformation {
-            line_number,
-            column_number,
-            line_text,
+/// A macro expansion: which macro was expanded, and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expansion {
+    /// The name of the expanded macro
+    pub macro_name: String,
+    /// The span of the invocation of the macro, which can itself come from another expansion
+    pub call_site: Span,
+    /// The span of the definition of the macro
+    pub def_site: Span,
+}
+
+/// `ExpansionTable` records every macro expansion, the spans produced by an expansion refer to it
+/// through their [`ExpansionId`].
+///
+/// A [`SourceMap`] owns one, so the diagnostics can show the macro invocations a span comes from.
+///
+/// # Example
+///
+/// ```
+/// use atlas_core::prelude::*;
+///
+/// let mut map = SourceMap::new();
+/// let file = map.add("main.atlas", "macro double(x) { x * 2 }\nlet y = double!(4);");
+/// let len = map.source(file).len();
+/// let call_site = Span::new(35, 45, file, len).unwrap();
+/// let expansion = map.add_expansion("double", call_site, Span::new(0, 25, file, len).unwrap());
+///
+/// // `x * 2` once expanded
+/// let expanded = Span::new(18, 23, file, len).unwrap().with_expansion(expansion);
+/// assert_eq!(expanded.call_site(map.expansions()), Some(call_site));
+/// assert_eq!(expanded.source_callsite(map.expansions()), call_site);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExpansionTable {
+    expansions: Vec<Expansion>,
+}
+
+impl ExpansionTable {
+    /// Creates an empty `ExpansionTable`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records an expansion of the macro `macro_name` and returns its id.
+    ///
+    /// # Panics
+    /// Panics if `call_site` comes from an expansion that isn't in the table.
+    pub fn add(&mut self, macro_name: impl Into<String>, call_site: Span, def_site: Span) -> ExpansionId {
+        assert!(
+            call_site.expansion.is_root() || self.get(call_site.expansion).is_some(),
+            "The call site of a macro must come from an expansion recorded before"
+        );
+        self.expansions.push(Expansion {
+            macro_name: macro_name.into(),
+            call_site,
+            def_site,
+        });
+        ExpansionId(u32::try_from(self.expansions.len()).expect("Too many macro expansions"))
+    }
+
+    /// Returns the expansion with the given id, `None` for `ExpansionId::ROOT` or an unknown id
+    pub fn get(&self, id: ExpansionId) -> Option<&Expansion> {
+        (id.0 as usize).checked_sub(1).and_then(|i| self.expansions.get(i))
+    }
+
+    /// Returns the number of expansions in the table
+    pub fn len(&self) -> usize {
+        self.expansions.len()
+    }
+
+    /// Returns `true` if no expansion was recorded
+    pub fn is_empty(&self) -> bool {
+        self.expansions.is_empty()
+    }
+}
+
+/// A value with the `Span` it comes from.
+///
+/// It lets a parser attach a span to anything (an identifier, an operator, a whole expression, ...)
+/// without defining a struct for it. It dereferences to the value.
+///
+/// # Example
+///
+/// ```
+/// use atlas_core::prelude::*;
+///
+/// let file = FileId::default();
+/// let number = Node::new("42", Span::new(8, 10, file, 11).unwrap());
+/// let parsed: Node<i64> = number.map(|text| text.parse().unwrap());
+/// assert_eq!(*parsed, 42);
+/// assert_eq!(parsed.span(), Span::new(8, 10, file, 11).unwrap());
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Node<T> {
+    /// The value
+    pub value: T,
+    /// Where the value comes from
+    pub span: Span,
+}
+
+impl<T> Node<T> {
+    /// Creates a new `Node`
+    pub fn new(value: T, span: Span) -> Self {
+        Node { value, span }
+    }
+
+    /// Transforms the value, keeping the span
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Node<U> {
+        Node {
+            value: f(self.value),
+            span: self.span,
+        }
+    }
+
+    /// Borrows the value, keeping the span
+    pub fn as_ref(&self) -> Node<&T> {
+        Node {
+            value: &self.value,
+            span: self.span,
+        }
+    }
+
+    /// Mutably borrows the value, keeping the span
+    pub fn as_mut(&mut self) -> Node<&mut T> {
+        Node {
+            value: &mut self.value,
+            span: self.span,
         }
     }
+
+    /// Returns the value, dropping the span
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for Node<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Node<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T> Spanned for Node<T> {
+    #[inline(always)]
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Node<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+/// The reasons a [`Span`] can't be built.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanError {
+    /// The start of the span is after its end
+    Inverted {
+        /// The start of the span
+        start: usize,
+        /// The end of the span
+        end: usize,
+    },
+    /// The span goes past the end of the source
+    OutOfBounds {
+        /// The end of the span
+        end: usize,
+        /// The length of the source
+        len: usize,
+    },
+    /// The spans to combine come from different files
+    DifferentFiles(FileId, FileId),
+    /// The span starts or ends in the middle of a char, at the given position
+    NotCharBoundary(usize),
+    /// The file of the span isn't in the `SourceMap`
+    UnknownFile(FileId),
+}
+
+impl fmt::Display for SpanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpanError::Inverted { start, end } => {
+                write!(f, "the span starts at {} but ends at {}", start, end)
+            }
+            SpanError::OutOfBounds { end, len } => write!(
+                f,
+                "the span ends at {} but the source is only {} bytes long",
+                end, len
+            ),
+            SpanError::DifferentFiles(a, b) => {
+                write!(f, "the spans come from different files: {} and {}", a, b)
+            }
+            SpanError::NotCharBoundary(pos) => {
+                write!(f, "the span has a bound in the middle of a char, at {}", pos)
+            }
+            SpanError::UnknownFile(file) => write!(f, "the file {} isn't in the SourceMap", file),
+        }
+    }
+}
+
+impl std::error::Error for SpanError {}
+
+/// The lines and columns covered by a [`Span`], as returned by [`Span::get_line_info`].
+///
+/// Lines and columns are 1-based, and columns are counted in chars rather than in bytes.
+/// The end is exclusive, like the end of the span.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LineInformation {
+    /// The line of the start of the span
+    pub start_line: usize,
+    /// The column of the start of the span
+    pub start_column: usize,
+    /// The line of the end of the span
+    pub end_line: usize,
+    /// The column of the end of the span
+    pub end_column: usize,
+    /// The text of the line(s) containing the span, without the final line ending
+    pub line_text: String,
 }
 
 impl fmt::Display for LineInformation {
@@ -136,7 +597,7 @@ impl fmt::Display for LineInformation {
         write!(
             f,
             "{}:{}:\n {}",
-            self.line_number, self.column_number, self.line_text
+            self.start_line, self.start_column, self.line_text
         )
     }
 }
@@ -157,11 +618,16 @@ impl fmt::Display for LineInformation {
 ///   This method is implemented using the `span` method and returns the start position.
 /// - `end(&self) -> usize`: Returns the ending position of the span.
 ///   This method is implemented using the `span` method and returns the end position.
+/// - `try_span(&self) -> Option<Span>`: Returns the span, or `None` for things without any
+///   (an empty `Vec`, `None`, ...) so they're left out when the spans of several children are combined.
+///
+/// `Spanned` is implemented for `Box<T>`, `Option<T>`, `Vec<T>`, slices and tuples, combining the
+/// spans of their elements. It can be derived with `#[derive(Spanned)]`, see below.
 ///
 /// # Example
 ///
 /// ```
-/// use atlas_core::utils::span::{Spanned, Span, BytePos};
+/// use atlas_core::utils::{source_map::FileId, span::{Spanned, Span, BytePos}};
 ///
 /// struct Token {
 ///     span: Span,
@@ -172,16 +638,67 @@ impl fmt::Display for LineInformation {
 /// #         self.span
 /// #     }
 /// # }
-/// # unsafe {
-/// //NB: "new_unchecked(usize, usize, char)" is an unsafe function, it's only used here as an example
-/// let token = Token { span: Span::new_unchecked(5, 10, "[")};
-/// assert_eq!(token.start(), 5);
-/// assert_eq!(token.end(), 10);
-/// # }
+/// let source = "let value = 5;";
+/// let token = Token { span: Span::new(4, 9, FileId::default(), source.len()).unwrap() };
+/// assert_eq!(token.start(), 4);
+/// assert_eq!(token.end(), 9);
+/// ```
+///
+/// Deriving it for the nodes of an AST:
+///
+/// ```
+/// use atlas_core::prelude::*;
+///
+/// #[derive(Spanned)]
+/// struct Ident {
+///     name: Intern<String>,
+///     span: Span,
+/// }
+///
+/// #[derive(Spanned)]
+/// struct Call {
+///     callee: Ident,
+///     args: Vec<Expr>,
+///     #[span]
+///     location: Span,
+/// }
+///
+/// #[derive(Spanned)]
+/// enum Expr {
+///     // Delegates to its only field
+///     Ident(Ident),
+///     Call(Box<Call>),
+///     Int { value: i64, span: Span },
+/// }
+///
+/// let file = FileId::default();
+/// let ident = Expr::Ident(Ident { name: Intern::new(String::from("x")), span: Span::new(0, 1, file, 3).unwrap() });
+/// assert_eq!(ident.span(), Span::new(0, 1, file, 3).unwrap());
+/// let int = Expr::Int { value: 5, span: Span::new(2, 3, file, 3).unwrap() };
+/// assert_eq!((&ident, &int).span(), Span::new(0, 3, file, 3).unwrap());
+/// ```
+///
+/// The derive needs to know where the span is:
+///
+/// ```compile_fail
+/// use atlas_core::prelude::*;
+///
+/// // error: `Binary` needs a field named `span` or marked with `#[span]`
+/// #[derive(Spanned)]
+/// struct Binary {
+///     lhs: Span,
+///     rhs: Span,
+/// }
 /// ```
 pub trait Spanned {
     /// Returns the `Span` representing the start and end positions of the object.
     fn span(&self) -> Span;
+    /// Returns the span of the object, `None` if it doesn't have any.
+    ///
+    /// It's `Some(self.span())` unless overridden by containers that can be empty.
+    fn try_span(&self) -> Option<Span> {
+        Some(self.span())
+    }
     /// Returns the starting position of the span.
     fn start(&self) -> usize {
         self.span().start.0
@@ -198,3 +715,92 @@ impl Spanned for Span {
         *self
     }
 }
+
+impl<T: Spanned + ?Sized> Spanned for &T {
+    #[inline(always)]
+    fn span(&self) -> Span {
+        (**self).span()
+    }
+    fn try_span(&self) -> Option<Span> {
+        (**self).try_span()
+    }
+}
+
+impl<T: Spanned + ?Sized> Spanned for Box<T> {
+    #[inline(always)]
+    fn span(&self) -> Span {
+        (**self).span()
+    }
+    fn try_span(&self) -> Option<Span> {
+        (**self).try_span()
+    }
+}
+
+/// Combines the spans of `children`, skipping the ones without any
+fn union_all<'a>(children: impl IntoIterator<Item = &'a dyn Spanned>) -> Option<Span> {
+    children
+        .into_iter()
+        .filter_map(Spanned::try_span)
+        .reduce(Span::union_span)
+}
+
+/// `None` has no span, so `span()` returns `Span::empty()` for it.
+impl<T: Spanned> Spanned for Option<T> {
+    fn span(&self) -> Span {
+        self.try_span().unwrap_or(Span::empty())
+    }
+    fn try_span(&self) -> Option<Span> {
+        self.as_ref()?.try_span()
+    }
+}
+
+/// The span goes from the first element to the last one, `span()` returns `Span::empty()` if there are none.
+///
+/// # Panics
+/// Panics if the elements come from different files.
+impl<T: Spanned> Spanned for [T] {
+    fn span(&self) -> Span {
+        self.try_span().unwrap_or(Span::empty())
+    }
+    fn try_span(&self) -> Option<Span> {
+        union_all(self.iter().map(|c| c as &dyn Spanned))
+    }
+}
+
+/// Same as the implementation for slices.
+impl<T: Spanned> Spanned for Vec<T> {
+    fn span(&self) -> Span {
+        self.as_slice().span()
+    }
+    fn try_span(&self) -> Option<Span> {
+        self.as_slice().try_span()
+    }
+}
+
+macro_rules! spanned_tuple {
+    ($($name:ident),+) => {
+        /// The span covers every element of the tuple, `span()` returns `Span::empty()` if none has a span.
+        ///
+        /// # Panics
+        /// Panics if the elements come from different files.
+        impl<$($name: Spanned),+> Spanned for ($($name,)+) {
+            fn span(&self) -> Span {
+                self.try_span().unwrap_or(Span::empty())
+            }
+            #[allow(non_snake_case)]
+            fn try_span(&self) -> Option<Span> {
+                let ($($name,)+) = self;
+                union_all([$($name as &dyn Spanned),+])
+            }
+        }
+    };
+}
+
+spanned_tuple!(A);
+spanned_tuple!(A, B);
+spanned_tuple!(A, B, C);
+spanned_tuple!(A, B, C, D);
+spanned_tuple!(A, B, C, D, E);
+spanned_tuple!(A, B, C, D, E, F);
+spanned_tuple!(A, B, C, D, E, F, G);
+spanned_tuple!(A, B, C, D, E, F, G, H);
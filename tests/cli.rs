@@ -0,0 +1,89 @@
+//! Runs the `atlas` binary, built with the `cli` feature.
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn atlas(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_atlas"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+fn stderr(output: &Output) -> &str {
+    std::str::from_utf8(&output.stderr).unwrap()
+}
+
+#[test]
+fn test_tokenize_stdin() {
+    let output = atlas(&["tokenize", "--format", "compact"], "let x: u64 = 1;");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "SoI@0..0\nKeyword@0..3\nIdentifier@4..5\nColon@5..6\nKeyword@7..10\nOpAssign@11..12\nLiteral@13..14\nSemicolon@14..15\nEoI@15..15\n"
+    );
+    // `-` and the `lex` alias read the standard input too
+    let output = atlas(&["lex", "-f", "compact", "-"], "x");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "SoI@0..0\nIdentifier@0..1\nEoI@1..1\n");
+}
+
+#[test]
+fn test_tokenize_files() {
+    let dir = std::env::temp_dir().join(format!("atlas-cli-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let main = dir.join("main.atlas");
+    std::fs::write(&main, "let x").unwrap();
+    let output = atlas(
+        &["tokenize", "-f", "compact", main.to_str().unwrap(), "-"],
+        "1",
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(stdout(&output).ends_with("main.atlas:\nSoI@0..0\nKeyword@0..3\nIdentifier@4..5\nEoI@5..5\n\n<stdin>:\nSoI@0..0\nLiteral@0..1\nEoI@1..1\n"));
+}
+
+#[test]
+fn test_tokenize_errors() {
+    // A literal too large is reported as a diagnostic, not a panic
+    let output = atlas(
+        &["tokenize", "--color", "never"],
+        "let x = 99999999999999999999",
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output)
+        .starts_with("error[A0003]: invalid literal `99999999999999999999`\n --> <stdin>:1:9\n"));
+
+    let output = atlas(&["tokenize", "--color", "never"], "let $");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("error[A0002]: unexpected character `$`"));
+
+    let output = atlas(&["tokenize", "does/not/exist.atlas"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("error: couldn't read `does/not/exist.atlas`"));
+
+    // The standard input can't be read twice
+    let output = atlas(&["tokenize", "-", "-"], "x");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(stdout(&output), "");
+    assert_eq!(stderr(&output), "error: `-` (the standard input) can only be given once\n");
+
+    // Bad arguments are reported by clap
+    let output = atlas(&["tokenize", "--format", "xml"], "");
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,9 @@
+//! Checks the errors reported at compile time for invalid `lexer_builder!` declarations.
+//!
+//! Run it with `TRYBUILD=overwrite cargo test --test ui` to update the `.stderr` files.
+
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
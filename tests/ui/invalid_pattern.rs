@@ -0,0 +1,39 @@
+use atlas_core::prelude::*;
+
+lexer_builder! {
+    DefaultSystem {
+        number: true,
+        symbol: true,
+        keyword: true,
+        string: true,
+        whitespace: {
+            allow_them: false,
+            use_system: true,
+        },
+    },
+    Symbols {
+        Single {
+            ';' => Semicolon,
+        },
+        Either {
+            '=' => '=' => OpEq, OpAssign,
+        }
+    },
+    Patterns {
+        r"#[0-9a-f" => Color,
+        r"\bfoo\b" => Foo,
+    },
+    Keyword {
+        "let", "if",
+    },
+    Number {
+        trailing {
+            "_i8" => i8 => I8,
+        },
+        float: true,
+        u_int: true,
+        int: true
+    },
+}
+
+fn main() {}